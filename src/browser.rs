@@ -0,0 +1,150 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use tui::widgets::ListState;
+
+use crate::paths;
+
+// fewer entries than for a typed path, a whole screen of folders is counted at once
+const PREVIEW_ENTRIES_LIMIT: usize = 2_000;
+
+pub struct BrowserEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub is_folder: bool,
+}
+
+// the folder browser of the sources tab, folders first then the audio files of the folder
+pub struct FolderBrowser {
+    pub folder: PathBuf,
+    pub entries: Vec<BrowserEntry>,
+    pub state: ListState,
+    pub error: Option<String>, // why the folder could not be read
+    recursive: bool,
+    extensions: Vec<String>,
+    // audio files under a folder, and whether the count is complete, counted when first shown
+    audio_counts: HashMap<PathBuf, (usize, bool)>,
+}
+impl FolderBrowser {
+    pub fn new(folder: PathBuf, recursive: bool, extensions: Vec<String>) -> Self {
+        let mut browser = Self {
+            folder: PathBuf::new(),
+            entries: vec![],
+            state: ListState::default(),
+            error: None,
+            recursive,
+            extensions,
+            audio_counts: HashMap::new(),
+        };
+        browser.open(folder, None);
+        browser
+    }
+}
+impl FolderBrowser {
+    // list `folder`, selecting the entry at `selected` if it is there
+    fn open(&mut self, folder: PathBuf, selected: Option<&Path>) {
+        self.entries.clear();
+        self.error = None;
+        match fs::read_dir(&folder) {
+            Ok(read_dir) => {
+                for dir_entry in read_dir.flatten() {
+                    let path = dir_entry.path();
+                    let name = dir_entry.file_name().to_string_lossy().to_string();
+                    // hidden entries are left out, like `ls` does
+                    if name.starts_with('.') {
+                        continue;
+                    }
+                    let is_folder = path.is_dir();
+                    if !is_folder && !self.is_audio_file(&path) {
+                        continue;
+                    }
+                    self.entries.push(BrowserEntry {
+                        path,
+                        name,
+                        is_folder,
+                    });
+                }
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+        self.entries.sort_by(|a, b| {
+            b.is_folder
+                .cmp(&a.is_folder)
+                .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        });
+        let selected_idx = selected
+            .and_then(|selected| self.entries.iter().position(|e| e.path == selected))
+            .unwrap_or(0);
+        self.state
+            .select(Some(selected_idx).filter(|_| !self.entries.is_empty()));
+        self.folder = folder;
+    }
+    fn is_audio_file(&self, path: &Path) -> bool {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        self.extensions.contains(&extension)
+    }
+    pub fn selected(&self) -> Option<&BrowserEntry> {
+        self.entries.get(self.state.selected()?)
+    }
+    pub fn select(&mut self, idx: usize) {
+        if idx < self.entries.len() {
+            self.state.select(Some(idx));
+        }
+    }
+    pub fn select_next(&mut self) {
+        if let Some(current) = self.state.selected() {
+            self.state.select(Some((current + 1) % self.entries.len()));
+        }
+    }
+    pub fn select_previous(&mut self) {
+        if let Some(current) = self.state.selected() {
+            let len = self.entries.len();
+            self.state.select(Some((current + len - 1) % len));
+        }
+    }
+    // into the selected folder, false if a file is selected
+    pub fn enter(&mut self) -> bool {
+        match self.selected() {
+            Some(entry) if entry.is_folder => {
+                let folder = entry.path.clone();
+                self.open(folder, None);
+                true
+            }
+            _ => false,
+        }
+    }
+    // to the parent folder, with the folder left selected
+    pub fn go_up(&mut self) {
+        if let Some(parent) = self.folder.parent() {
+            let left = self.folder.clone();
+            self.open(parent.to_path_buf(), Some(&left));
+        }
+    }
+    // the folder a source would be added from: the selected folder, or the one being shown
+    pub fn target_folder(&self) -> &Path {
+        match self.selected() {
+            Some(entry) if entry.is_folder => &entry.path,
+            _ => &self.folder,
+        }
+    }
+    pub fn audio_count(&mut self, folder: &Path) -> (usize, bool) {
+        if let Some(count) = self.audio_counts.get(folder) {
+            return *count;
+        }
+        let count = paths::count_audio_files(
+            folder,
+            self.recursive,
+            &self.extensions,
+            PREVIEW_ENTRIES_LIMIT,
+        );
+        self.audio_counts.insert(folder.to_path_buf(), count);
+        count
+    }
+}
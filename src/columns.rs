@@ -0,0 +1,239 @@
+use std::{cmp::Ordering, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tui::layout::Constraint;
+
+use crate::{
+    stats::{self, SongStats, SongStatsDB},
+    Song,
+};
+
+// columns of the songs table, which ones are shown is read from setting.json ("song_columns")
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SongColumn {
+    TrackNumber,
+    Title,
+    Artist,
+    Album,
+    Duration,
+    Format,
+    Size,
+    PlayCount,
+    SkipCount,
+    Rating,
+    Favorite,
+    LastPlayed,
+}
+impl SongColumn {
+    pub fn default_columns() -> Vec<SongColumn> {
+        vec![
+            SongColumn::TrackNumber,
+            SongColumn::Title,
+            SongColumn::Artist,
+            SongColumn::Album,
+            SongColumn::Duration,
+            SongColumn::Rating,
+            SongColumn::PlayCount,
+        ]
+    }
+    pub fn title(&self) -> &'static str {
+        match self {
+            SongColumn::TrackNumber => "#",
+            SongColumn::Title => "Title",
+            SongColumn::Artist => "Artist",
+            SongColumn::Album => "Album",
+            SongColumn::Duration => "Time",
+            SongColumn::Format => "Format",
+            SongColumn::Size => "Size",
+            SongColumn::PlayCount => "Plays",
+            SongColumn::SkipCount => "Skips",
+            SongColumn::Rating => "Rating",
+            SongColumn::Favorite => "Fav",
+            SongColumn::LastPlayed => "Played",
+        }
+    }
+    pub fn width(&self) -> Constraint {
+        match self {
+            SongColumn::TrackNumber => Constraint::Length(4),
+            SongColumn::Title => Constraint::Percentage(35),
+            SongColumn::Artist => Constraint::Percentage(20),
+            SongColumn::Album => Constraint::Percentage(20),
+            SongColumn::Duration => Constraint::Length(6),
+            SongColumn::Format => Constraint::Length(6),
+            SongColumn::Size => Constraint::Length(9),
+            SongColumn::PlayCount => Constraint::Length(6),
+            SongColumn::SkipCount => Constraint::Length(6),
+            SongColumn::Rating => Constraint::Length(8),
+            SongColumn::Favorite => Constraint::Length(4),
+            SongColumn::LastPlayed => Constraint::Length(10),
+        }
+    }
+    pub fn cell_text(&self, song: &Song, song_stats: SongStats) -> String {
+        match self {
+            SongColumn::TrackNumber => song.track.map(|t| t.to_string()).unwrap_or_default(),
+            SongColumn::Title => song.display_title(),
+            SongColumn::Artist => song.artist.clone().unwrap_or_default(),
+            SongColumn::Album => song.album.clone().unwrap_or_default(),
+            SongColumn::Duration => song.duration.map(format_duration).unwrap_or_default(),
+            SongColumn::Format => song.format.clone(),
+            SongColumn::Size => format_size(song.size),
+            SongColumn::PlayCount => song_stats.play_count.to_string(),
+            SongColumn::SkipCount => song_stats.skip_count.to_string(),
+            SongColumn::Rating => "★".repeat(song_stats.rating as usize),
+            SongColumn::Favorite => if song_stats.favorite { "♥" } else { "" }.to_string(),
+            SongColumn::LastPlayed => song_stats
+                .last_played
+                .map(stats::format_time_ago)
+                .unwrap_or_default(),
+        }
+    }
+    pub fn compare(&self, a: &Song, b: &Song, song_stats: &SongStatsDB) -> Ordering {
+        let text = |s: &Option<String>| s.as_ref().map(|s| s.to_lowercase());
+        let (a_stats, b_stats) = (song_stats.get(&a.path), song_stats.get(&b.path));
+        match self {
+            SongColumn::TrackNumber => a.track.cmp(&b.track),
+            SongColumn::Title => a
+                .display_title()
+                .to_lowercase()
+                .cmp(&b.display_title().to_lowercase()),
+            SongColumn::Artist => text(&a.artist).cmp(&text(&b.artist)),
+            SongColumn::Album => text(&a.album)
+                .cmp(&text(&b.album))
+                .then(a.track.cmp(&b.track)),
+            SongColumn::Duration => a.duration.cmp(&b.duration),
+            SongColumn::Format => a.format.cmp(&b.format),
+            SongColumn::Size => a.size.cmp(&b.size),
+            SongColumn::PlayCount => a_stats.play_count.cmp(&b_stats.play_count),
+            SongColumn::SkipCount => a_stats.skip_count.cmp(&b_stats.skip_count),
+            SongColumn::Rating => a_stats.rating.cmp(&b_stats.rating),
+            SongColumn::Favorite => a_stats.favorite.cmp(&b_stats.favorite),
+            SongColumn::LastPlayed => a_stats.last_played.cmp(&b_stats.last_played),
+        }
+    }
+}
+
+// sort choice of a playlist, saved in playlist.json ("playlist_sort_orders")
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SongSort {
+    pub column: SongColumn,
+    pub ascending: bool,
+}
+impl SongSort {
+    pub fn compare(&self, a: &Song, b: &Song, song_stats: &SongStatsDB) -> Ordering {
+        let ordering = self.column.compare(a, b, song_stats);
+        if self.ascending {
+            ordering
+        } else {
+            ordering.reverse()
+        }
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(songs: &[Song], sort: SongSort) -> Vec<String> {
+        let mut songs = songs.to_vec();
+        songs.sort_by(|a, b| sort.compare(a, b, &SongStatsDB::default()));
+        songs.into_iter().map(|s| s.name).collect()
+    }
+
+    #[test]
+    fn format_duration_shows_hours_only_when_needed() {
+        assert_eq!(format_duration(Duration::from_secs(0)), "0:00");
+        assert_eq!(format_duration(Duration::from_secs(65)), "1:05");
+        assert_eq!(format_duration(Duration::from_secs(3599)), "59:59");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1:00:00");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1:02:05");
+    }
+
+    #[test]
+    fn sort_by_title_ignores_case_and_falls_back_to_the_file_name() {
+        let songs = [
+            Song::untagged("/music/C.mp3"),
+            Song {
+                title: Some("apple".to_string()),
+                ..Song::untagged("/music/b.mp3")
+            },
+            Song::untagged("/music/a.mp3"),
+        ];
+        let ascending = SongSort {
+            column: SongColumn::Title,
+            ascending: true,
+        };
+        assert_eq!(sorted(&songs, ascending), ["a.mp3", "b.mp3", "C.mp3"]);
+        let descending = SongSort {
+            ascending: false,
+            ..ascending
+        };
+        assert_eq!(sorted(&songs, descending), ["C.mp3", "b.mp3", "a.mp3"]);
+    }
+
+    #[test]
+    fn sort_by_album_keeps_the_track_order() {
+        let song = |path, album: &str, track| Song {
+            album: Some(album.to_string()),
+            track: Some(track),
+            ..Song::untagged(path)
+        };
+        let songs = [
+            song("/music/3.mp3", "b", 1),
+            song("/music/2.mp3", "A", 2),
+            song("/music/1.mp3", "a", 1),
+        ];
+        let sort = SongSort {
+            column: SongColumn::Album,
+            ascending: true,
+        };
+        assert_eq!(sorted(&songs, sort), ["1.mp3", "2.mp3", "3.mp3"]);
+    }
+
+    #[test]
+    fn songs_without_the_tag_sort_first() {
+        let songs = [
+            Song {
+                artist: Some("artist".to_string()),
+                ..Song::untagged("/music/tagged.mp3")
+            },
+            Song::untagged("/music/untagged.mp3"),
+        ];
+        let sort = SongSort {
+            column: SongColumn::Artist,
+            ascending: true,
+        };
+        assert_eq!(sorted(&songs, sort), ["untagged.mp3", "tagged.mp3"]);
+    }
+
+    #[test]
+    fn sizes_use_the_largest_unit_below_1024() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MB");
+    }
+}
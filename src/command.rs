@@ -0,0 +1,228 @@
+use std::time::Duration;
+
+use crate::{keymap::Action, paths, PlayMode};
+
+// a `:` command line, parsed
+pub enum Command {
+    Play(Option<String>), // resume, or play the best match of a query
+    Queue(String),
+    NewPlaylist(String),
+    AddSource(String),
+    Seek(Duration),
+    Volume(u8),
+    Mode(PlayMode),
+    Rescan,
+    Action(Action), // any keymap action by name, ":next_song"
+}
+
+const COMMAND_NAMES: [&str; 8] = [
+    "play", "queue", "playlist", "source", "seek", "vol", "mode", "rescan",
+];
+const MODE_NAMES: [(&str, PlayMode); 4] = [
+    ("single", PlayMode::SingleLoop),
+    ("loop", PlayMode::ListLoop),
+    ("shuffle", PlayMode::ListRandom),
+    ("shuffle_all", PlayMode::AllListRandom),
+];
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim();
+        let (name, args) = match line.split_once(char::is_whitespace) {
+            Some((name, args)) => (name, args.trim()),
+            None => (line, ""),
+        };
+        let (sub_name, sub_args) = match args.split_once(char::is_whitespace) {
+            Some((sub_name, sub_args)) => (sub_name, sub_args.trim()),
+            None => (args, ""),
+        };
+        match name {
+            "play" if args.is_empty() => Ok(Command::Play(None)),
+            "play" => Ok(Command::Play(Some(args.to_string()))),
+            "queue" if args.is_empty() => Err("usage: :queue <query>".to_string()),
+            "queue" => Ok(Command::Queue(args.to_string())),
+            "playlist" => match (sub_name, sub_args) {
+                ("new", "") | ("", _) => Err("usage: :playlist new <name>".to_string()),
+                ("new", name) => Ok(Command::NewPlaylist(name.to_string())),
+                (sub_name, _) => Err(format!("unknown playlist command: {}", sub_name)),
+            },
+            "source" => match (sub_name, sub_args) {
+                ("add", "") | ("", _) => Err("usage: :source add <path>".to_string()),
+                ("add", path) => Ok(Command::AddSource(path.to_string())),
+                (sub_name, _) => Err(format!("unknown source command: {}", sub_name)),
+            },
+            "seek" => parse_time(args)
+                .map(Command::Seek)
+                .ok_or_else(|| "usage: :seek <m:ss>".to_string()),
+            "vol" => match args.parse::<u8>() {
+                Ok(volume) if volume <= 100 => Ok(Command::Volume(volume)),
+                _ => Err("usage: :vol <0-100>".to_string()),
+            },
+            "mode" => MODE_NAMES
+                .iter()
+                .find(|(mode_name, _)| *mode_name == args)
+                .map(|(_, mode)| Command::Mode(*mode))
+                .ok_or_else(|| {
+                    let names: Vec<&str> = MODE_NAMES.iter().map(|(n, _)| *n).collect();
+                    format!("usage: :mode <{}>", names.join("|"))
+                }),
+            "rescan" => Ok(Command::Rescan),
+            "" => Err("empty command".to_string()),
+            "q" => Ok(Command::Action(Action::Quit)),
+            _ => Action::from_name(name)
+                .map(Command::Action)
+                .ok_or_else(|| format!("unknown command: {}", name)),
+        }
+    }
+}
+
+// "90", "1:30", "1:02:03"
+fn parse_time(text: &str) -> Option<Duration> {
+    let mut secs: u64 = 0;
+    for part in text.split(':') {
+        secs = secs
+            .checked_mul(60)?
+            .checked_add(part.parse::<u64>().ok()?)?;
+    }
+    Some(Duration::from_secs(secs))
+}
+
+// candidates for the word under the cursor at the end of `line`,
+// with the byte offset that word starts at
+pub fn completions(line: &str) -> (usize, Vec<String>) {
+    let word_start = line.rfind(' ').map_or(0, |i| i + 1);
+    let word = &line[word_start..];
+    let words: Vec<&str> = line[..word_start].split_whitespace().collect();
+    let candidates: Vec<String> = match words.as_slice() {
+        [] => COMMAND_NAMES
+            .iter()
+            .map(|n| n.to_string())
+            .chain(Action::ALL.iter().map(|a| a.name()))
+            .collect(),
+        ["mode"] => MODE_NAMES.iter().map(|(n, _)| n.to_string()).collect(),
+        ["playlist"] => vec!["new".to_string()],
+        ["source"] => vec!["add".to_string()],
+        ["source", "add", ..] => {
+            // the path is the rest of the line, it may have spaces
+            let path_start = line.find("add").unwrap() + 4;
+            let path_start = path_start.min(line.len());
+            return (path_start, paths::folder_completions(&line[path_start..]));
+        }
+        _ => vec![],
+    };
+    let candidates = candidates
+        .into_iter()
+        .filter(|c| c.starts_with(word))
+        .collect();
+    (word_start, candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(line: &str) -> String {
+        match Command::parse(line) {
+            Err(error) => error,
+            Ok(_) => panic!("{} parsed", line),
+        }
+    }
+
+    #[test]
+    fn seek_takes_seconds_or_clock_times() {
+        let seek = |line| match Command::parse(line) {
+            Ok(Command::Seek(position)) => position.as_secs(),
+            _ => panic!("{} is not a seek", line),
+        };
+        assert_eq!(seek("seek 90"), 90);
+        assert_eq!(seek("seek 1:30"), 90);
+        assert_eq!(seek("seek 1:02:03"), 3723);
+        assert_eq!(error("seek"), "usage: :seek <m:ss>");
+        assert_eq!(error("seek 1:xx"), "usage: :seek <m:ss>");
+        assert_eq!(error("seek -5"), "usage: :seek <m:ss>");
+        // too long for the seconds to count
+        assert_eq!(error("seek 999999999999999999:0:0"), "usage: :seek <m:ss>");
+    }
+
+    #[test]
+    fn volume_is_up_to_100() {
+        assert!(matches!(Command::parse("vol 0"), Ok(Command::Volume(0))));
+        assert!(matches!(
+            Command::parse("vol 100"),
+            Ok(Command::Volume(100))
+        ));
+        assert_eq!(error("vol 101"), "usage: :vol <0-100>");
+        assert_eq!(error("vol loud"), "usage: :vol <0-100>");
+    }
+
+    #[test]
+    fn modes_are_named() {
+        assert!(matches!(
+            Command::parse("mode shuffle_all"),
+            Ok(Command::Mode(PlayMode::AllListRandom))
+        ));
+        assert!(matches!(
+            Command::parse("mode single"),
+            Ok(Command::Mode(PlayMode::SingleLoop))
+        ));
+        assert_eq!(
+            error("mode random"),
+            "usage: :mode <single|loop|shuffle|shuffle_all>"
+        );
+    }
+
+    #[test]
+    fn names_keep_their_spaces() {
+        match Command::parse("  playlist new  Road trip  mix ") {
+            Ok(Command::NewPlaylist(name)) => assert_eq!(name, "Road trip  mix"),
+            _ => panic!("not a new playlist"),
+        }
+        match Command::parse("play blue monday") {
+            Ok(Command::Play(Some(query))) => assert_eq!(query, "blue monday"),
+            _ => panic!("not a play"),
+        }
+        assert!(matches!(Command::parse("play"), Ok(Command::Play(None))));
+    }
+
+    #[test]
+    fn usage_errors() {
+        assert_eq!(error(""), "empty command");
+        assert_eq!(error("queue"), "usage: :queue <query>");
+        assert_eq!(error("playlist"), "usage: :playlist new <name>");
+        assert_eq!(error("playlist new"), "usage: :playlist new <name>");
+        assert_eq!(
+            error("playlist rename x"),
+            "unknown playlist command: rename"
+        );
+        assert_eq!(error("source"), "usage: :source add <path>");
+        assert_eq!(error("source rm /music"), "unknown source command: rm");
+        assert_eq!(error("bogus"), "unknown command: bogus");
+    }
+
+    #[test]
+    fn actions_run_by_name() {
+        assert!(matches!(
+            Command::parse("q"),
+            Ok(Command::Action(Action::Quit))
+        ));
+        assert!(matches!(
+            Command::parse("next_song"),
+            Ok(Command::Action(Action::NextSong))
+        ));
+        assert!(matches!(Command::parse("rescan"), Ok(Command::Rescan)));
+    }
+
+    #[test]
+    fn completions_follow_the_words_typed() {
+        assert_eq!(completions("mo"), (0, vec!["mode".to_string()]));
+        assert_eq!(
+            completions("mode shuffle"),
+            (5, vec!["shuffle".to_string(), "shuffle_all".to_string()])
+        );
+        assert_eq!(completions("playlist "), (9, vec!["new".to_string()]));
+        assert_eq!(completions("vol 1"), (4, vec![]));
+        let (start, candidates) = completions("next");
+        assert_eq!(start, 0);
+        assert!(candidates.contains(&"next_song".to_string()));
+    }
+}
@@ -0,0 +1,369 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs::File,
+    hash::Hasher,
+    io::{BufReader, Read},
+};
+
+use tui::widgets::ListState;
+
+use crate::Song;
+
+// why the songs of a group are considered the same track, strongest first
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DuplicateReason {
+    Content,
+    SizeAndDuration,
+    Tags,
+}
+impl DuplicateReason {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            DuplicateReason::Content => "Identical content",
+            DuplicateReason::SizeAndDuration => "Same size and duration",
+            DuplicateReason::Tags => "Same title and artist",
+        }
+    }
+}
+
+pub struct DuplicateGroup {
+    pub reason: DuplicateReason,
+    pub songs: Vec<Song>,
+}
+
+// the duplicates view of the home tab, one header row per group followed by its songs
+pub struct DuplicatesView {
+    pub groups: Vec<DuplicateGroup>,
+    pub rows: Vec<(usize, Option<usize>)>, // (group idx, song idx), no song idx for a group header
+    pub state: ListState,
+}
+impl DuplicatesView {
+    pub fn new(groups: Vec<DuplicateGroup>) -> Self {
+        let mut view = Self {
+            groups,
+            rows: vec![],
+            state: ListState::default(),
+        };
+        view.build_rows();
+        view
+    }
+}
+impl DuplicatesView {
+    fn build_rows(&mut self) {
+        self.rows = self
+            .groups
+            .iter()
+            .enumerate()
+            .flat_map(|(group_idx, group)| {
+                std::iter::once((group_idx, None))
+                    .chain((0..group.songs.len()).map(move |song_idx| (group_idx, Some(song_idx))))
+            })
+            .collect();
+        self.state.select(
+            self.rows
+                .iter()
+                .position(|(_, song_idx)| song_idx.is_some()),
+        );
+    }
+    pub fn select_next(&mut self) {
+        if let Some(current) = self.state.selected() {
+            let next = (1..self.rows.len())
+                .map(|offset| (current + offset) % self.rows.len())
+                .find(|i| self.rows[*i].1.is_some());
+            self.state.select(next.or(Some(current)));
+        }
+    }
+    pub fn select_previous(&mut self) {
+        if let Some(current) = self.state.selected() {
+            let previous = (1..self.rows.len())
+                .map(|offset| (current + self.rows.len() - offset) % self.rows.len())
+                .find(|i| self.rows[*i].1.is_some());
+            self.state.select(previous.or(Some(current)));
+        }
+    }
+    // the selected copy and the other copies of its group
+    pub fn selected_copy(&self) -> Option<(Song, Vec<Song>)> {
+        let (group_idx, song_idx) = self.rows.get(self.state.selected()?).copied()?;
+        let song_idx = song_idx?;
+        let songs = &self.groups[group_idx].songs;
+        let others = songs
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != song_idx)
+            .map(|(_, s)| s.clone())
+            .collect();
+        Some((songs[song_idx].clone(), others))
+    }
+    // drop the group of the selected copy once it has been resolved
+    pub fn remove_selected_group(&mut self) {
+        if let Some((group_idx, _)) = self.state.selected().map(|i| self.rows[i]) {
+            self.groups.remove(group_idx);
+            self.build_rows();
+        }
+    }
+}
+
+// group songs that are probably the same track under different paths.
+// a song linked to another one by any of the reasons ends up in the same group
+pub fn find_duplicates(songs: &[Song]) -> Vec<DuplicateGroup> {
+    let mut parents: Vec<usize> = (0..songs.len()).collect();
+    let mut reasons: Vec<Option<DuplicateReason>> = vec![None; songs.len()];
+
+    // content hash, only for files whose size collides with another file
+    let mut by_size: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (i, song) in songs.iter().enumerate() {
+        by_size.entry(song.size).or_default().push(i);
+    }
+    let mut by_content: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+    for idxs in by_size.values().filter(|idxs| idxs.len() > 1) {
+        for &i in idxs {
+            if let Some(hash) = content_hash(&songs[i]) {
+                by_content.entry((songs[i].size, hash)).or_default().push(i);
+            }
+        }
+    }
+    link_all(
+        &mut parents,
+        &mut reasons,
+        by_content,
+        DuplicateReason::Content,
+    );
+
+    // size and duration
+    let mut by_size_duration = HashMap::new();
+    for (i, song) in songs.iter().enumerate() {
+        if let Some(duration) = song.duration {
+            by_size_duration
+                .entry((song.size, duration))
+                .or_insert_with(Vec::new)
+                .push(i);
+        }
+    }
+    link_all(
+        &mut parents,
+        &mut reasons,
+        by_size_duration,
+        DuplicateReason::SizeAndDuration,
+    );
+
+    // title and artist tags
+    let mut by_tags = HashMap::new();
+    for (i, song) in songs.iter().enumerate() {
+        if let (Some(title), Some(artist)) = (&song.title, &song.artist) {
+            by_tags
+                .entry((title.trim().to_lowercase(), artist.trim().to_lowercase()))
+                .or_insert_with(Vec::new)
+                .push(i);
+        }
+    }
+    link_all(&mut parents, &mut reasons, by_tags, DuplicateReason::Tags);
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..songs.len() {
+        let root = find_root(&mut parents, i);
+        groups.entry(root).or_default().push(i);
+    }
+    let mut groups: Vec<DuplicateGroup> = groups
+        .into_iter()
+        .filter(|(_, idxs)| idxs.len() > 1)
+        .map(|(root, idxs)| DuplicateGroup {
+            reason: reasons[root].unwrap_or(DuplicateReason::Tags),
+            songs: idxs.into_iter().map(|i| songs[i].clone()).collect(),
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        a.reason
+            .cmp(&b.reason)
+            .then_with(|| a.songs[0].path.cmp(&b.songs[0].path))
+    });
+    groups
+}
+
+fn find_root(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+fn link_all<K>(
+    parents: &mut [usize],
+    reasons: &mut [Option<DuplicateReason>],
+    buckets: HashMap<K, Vec<usize>>,
+    reason: DuplicateReason,
+) {
+    for idxs in buckets.values().filter(|idxs| idxs.len() > 1) {
+        for &i in &idxs[1..] {
+            let a = find_root(parents, idxs[0]);
+            let b = find_root(parents, i);
+            // keep the strongest reason on the new root
+            let strongest = [reasons[a], reasons[b], Some(reason)]
+                .into_iter()
+                .flatten()
+                .min();
+            if a != b {
+                parents[b] = a;
+            }
+            reasons[a] = strongest;
+        }
+    }
+}
+
+fn content_hash(song: &Song) -> Option<u64> {
+    let mut reader = BufReader::new(File::open(&song.path).ok()?);
+    let mut hasher = DefaultHasher::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf).ok()?;
+        if n == 0 {
+            break;
+        }
+        hasher.write(&buf[..n]);
+    }
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, time::Duration};
+
+    use super::*;
+    use crate::scratch_dir;
+
+    fn tagged(path: &str, title: &str, artist: &str) -> Song {
+        Song {
+            title: Some(title.to_string()),
+            artist: Some(artist.to_string()),
+            ..Song::untagged(path)
+        }
+    }
+
+    fn sized(path: &str, size: u64, duration: Option<u64>) -> Song {
+        Song {
+            size,
+            duration: duration.map(Duration::from_secs),
+            ..Song::untagged(path)
+        }
+    }
+
+    fn written(dir: &Path, name: &str, content: &[u8]) -> Song {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        Song {
+            size: content.len() as u64,
+            ..Song::untagged(path.to_str().unwrap())
+        }
+    }
+
+    fn names(group: &DuplicateGroup) -> Vec<&str> {
+        let mut names: Vec<&str> = group.songs.iter().map(|s| s.name.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn same_title_and_artist_ignoring_case_and_spaces() {
+        let songs = [
+            tagged("/missing/a.mp3", "Song", "Artist"),
+            sized("/missing/single.mp3", 1, None),
+            tagged("/missing/b.mp3", " song", "ARTIST "),
+            tagged("/missing/c.mp3", "Song", "Other"),
+        ];
+        let groups = find_duplicates(&songs);
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].reason == DuplicateReason::Tags);
+        assert_eq!(names(&groups[0]), ["a.mp3", "b.mp3"]);
+    }
+
+    #[test]
+    fn same_size_and_duration() {
+        let songs = [
+            sized("/missing/a.mp3", 100, Some(60)),
+            sized("/missing/b.mp3", 100, Some(60)),
+            sized("/missing/c.mp3", 100, Some(61)),
+            // no duration is not the same duration
+            sized("/missing/d.mp3", 100, None),
+        ];
+        let groups = find_duplicates(&songs);
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].reason == DuplicateReason::SizeAndDuration);
+        assert_eq!(names(&groups[0]), ["a.mp3", "b.mp3"]);
+    }
+
+    #[test]
+    fn identical_content() {
+        let dir = scratch_dir("duplicates-content");
+        let songs = [
+            written(&dir, "a.mp3", b"same bytes"),
+            written(&dir, "b.mp3", b"same bytes"),
+            written(&dir, "c.mp3", b"other byte"),
+        ];
+        let groups = find_duplicates(&songs);
+        assert_eq!(groups.len(), 1);
+        assert!(groups[0].reason == DuplicateReason::Content);
+        assert_eq!(names(&groups[0]), ["a.mp3", "b.mp3"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_files_of_the_same_size_are_not_duplicates() {
+        let songs = [
+            sized("/missing/a.mp3", 100, None),
+            sized("/missing/b.mp3", 100, None),
+        ];
+        assert!(find_duplicates(&songs).is_empty());
+    }
+
+    #[test]
+    fn linked_songs_share_a_group_with_the_strongest_reason() {
+        let dir = scratch_dir("duplicates-linked");
+        let a = Song {
+            title: Some("Song".to_string()),
+            artist: Some("Artist".to_string()),
+            ..written(&dir, "a.mp3", b"same bytes")
+        };
+        let b = written(&dir, "b.mp3", b"same bytes");
+        let songs = [
+            a,
+            b,
+            tagged("/missing/c.mp3", "Song", "Artist"),
+            tagged("/missing/d.mp3", "Other", "Artist"),
+            tagged("/missing/e.mp3", "Other", "Artist"),
+        ];
+        let groups = find_duplicates(&songs);
+        assert_eq!(groups.len(), 2);
+        assert!(groups[0].reason == DuplicateReason::Content);
+        assert_eq!(names(&groups[0]), ["a.mp3", "b.mp3", "c.mp3"]);
+        assert!(groups[1].reason == DuplicateReason::Tags);
+        assert_eq!(names(&groups[1]), ["d.mp3", "e.mp3"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn the_view_selects_songs_and_skips_group_headers() {
+        let songs = [
+            tagged("/missing/a.mp3", "A", "Artist"),
+            tagged("/missing/b.mp3", "A", "Artist"),
+            tagged("/missing/c.mp3", "C", "Artist"),
+            tagged("/missing/d.mp3", "C", "Artist"),
+        ];
+        let mut view = DuplicatesView::new(find_duplicates(&songs));
+        assert_eq!(view.state.selected(), Some(1));
+        view.select_next();
+        view.select_next();
+        assert_eq!(view.state.selected(), Some(4));
+        view.select_next();
+        view.select_next();
+        assert_eq!(view.state.selected(), Some(1));
+        view.select_previous();
+        assert_eq!(view.state.selected(), Some(5));
+        let (kept, others) = view.selected_copy().unwrap();
+        assert_eq!(kept.name, "d.mp3");
+        assert_eq!(others.len(), 1);
+        view.remove_selected_group();
+        assert_eq!(view.groups.len(), 1);
+        assert_eq!(view.rows, [(0, None), (0, Some(0)), (0, Some(1))]);
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{stats, Song};
+
+// one playback of a song, a line of history.jsonl
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub song_path: String,
+    pub started_at: u64, // unix timestamp in seconds
+    pub listened_secs: u64,
+    pub completed: bool, // false if the song was skipped or stopped
+    #[serde(default)]
+    pub stopped: bool, // playback stopped without the song being skipped
+}
+
+// how a playback ended, only skips count against a song
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackEnd {
+    Finished,
+    Skipped,
+    Stopped,
+}
+
+// history.jsonl, appended by the playback thread and read by the ui
+pub struct ListeningHistory {
+    pub entries: Vec<HistoryEntry>, // oldest first
+    file_path: PathBuf,
+    // bumped on every append so the ui knows when to refresh
+    pub revision: u64,
+}
+impl ListeningHistory {
+    pub fn load(file_path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&file_path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        Self {
+            entries,
+            file_path,
+            revision: 0,
+        }
+    }
+}
+impl ListeningHistory {
+    // the entry is kept for this session even if history.jsonl can not be written
+    pub fn append(&mut self, entry: HistoryEntry) -> io::Result<()> {
+        let line = serde_json::to_string(&entry).unwrap();
+        self.entries.push(entry);
+        self.revision += 1;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file_path)?;
+        writeln!(file, "{}", line)
+    }
+}
+
+// how long the playing song has been listened to, pauses excluded
+pub struct ListenClock {
+    pub started_at: u64, // unix timestamp in seconds
+    listened: Duration,
+    resumed_at: Option<Instant>,
+}
+impl ListenClock {
+    pub fn start() -> Self {
+        Self {
+            started_at: stats::now(),
+            listened: Duration::ZERO,
+            resumed_at: Some(Instant::now()),
+        }
+    }
+}
+impl ListenClock {
+    pub fn pause(&mut self) {
+        if let Some(resumed_at) = self.resumed_at.take() {
+            self.listened += resumed_at.elapsed();
+        }
+    }
+    pub fn resume(&mut self) {
+        if self.resumed_at.is_none() {
+            self.resumed_at = Some(Instant::now());
+        }
+    }
+    pub fn listened(&self) -> Duration {
+        self.listened + self.resumed_at.map_or(Duration::ZERO, |r| r.elapsed())
+    }
+}
+
+// listening stats of the stats tab, computed from the history entries since a time
+pub struct ListeningReport {
+    pub total_listened: Duration,
+    pub plays: usize,
+    pub skips: usize,
+    pub top_artists: Vec<(String, usize)>,
+    pub top_albums: Vec<(String, usize)>,
+    pub top_tracks: Vec<(String, usize)>,
+    pub most_skipped: Vec<(String, usize)>,
+    pub minutes_by_hour: [u64; 24], // local hour of day -> minutes listened
+}
+impl ListeningReport {
+    // `songs` maps the song paths of the library to their tags
+    pub fn compute(
+        entries: &[HistoryEntry],
+        since: Option<u64>,
+        songs: &HashMap<String, Song>,
+    ) -> Self {
+        const TOP_LIMIT: usize = 10;
+        // a song counts as played if it was listened to the end or for half a minute
+        const PLAYED_SECS: u64 = 30;
+        let mut report = Self {
+            total_listened: Duration::ZERO,
+            plays: 0,
+            skips: 0,
+            top_artists: vec![],
+            top_albums: vec![],
+            top_tracks: vec![],
+            most_skipped: vec![],
+            minutes_by_hour: [0; 24],
+        };
+        let mut artists: HashMap<String, usize> = HashMap::new();
+        let mut albums: HashMap<String, usize> = HashMap::new();
+        let mut tracks: HashMap<String, usize> = HashMap::new();
+        let mut skipped: HashMap<String, usize> = HashMap::new();
+        let mut seconds_by_hour = [0u64; 24];
+        for entry in entries
+            .iter()
+            .filter(|e| since.is_none_or(|since| e.started_at >= since))
+        {
+            report.total_listened += Duration::from_secs(entry.listened_secs);
+            seconds_by_hour[local_hour(entry.started_at)] += entry.listened_secs;
+            let song = songs.get(&entry.song_path);
+            let track = match song {
+                Some(song) => match &song.artist {
+                    Some(artist) => format!("{} - {}", song.display_title(), artist),
+                    None => song.display_title(),
+                },
+                None => entry.song_path.clone(),
+            };
+            if !entry.completed && !entry.stopped {
+                report.skips += 1;
+                *skipped.entry(track.clone()).or_default() += 1;
+            }
+            if entry.completed || entry.listened_secs >= PLAYED_SECS {
+                report.plays += 1;
+                *tracks.entry(track).or_default() += 1;
+                if let Some(artist) = song.and_then(|s| s.artist.clone()) {
+                    *artists.entry(artist).or_default() += 1;
+                }
+                if let Some(album) = song.and_then(|s| s.album.clone()) {
+                    *albums.entry(album).or_default() += 1;
+                }
+            }
+        }
+        let top = |counts: HashMap<String, usize>| {
+            let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+            counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            counts.truncate(TOP_LIMIT);
+            counts
+        };
+        report.top_artists = top(artists);
+        report.top_albums = top(albums);
+        report.top_tracks = top(tracks);
+        report.most_skipped = top(skipped);
+        report.minutes_by_hour = seconds_by_hour.map(|secs| secs / 60);
+        report
+    }
+}
+
+// hour of the day in the local timezone
+#[cfg(unix)]
+fn local_hour(timestamp: u64) -> usize {
+    let time = timestamp as libc::time_t;
+    // safety: localtime_r only writes into the tm we own
+    let tm = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&time, &mut tm);
+        tm
+    };
+    (tm.tm_hour as usize).min(23)
+}
+#[cfg(windows)]
+fn local_hour(timestamp: u64) -> usize {
+    let time = timestamp as libc::time_t;
+    // safety: localtime_s only writes into the tm we own
+    let tm = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_s(&mut tm, &time);
+        tm
+    };
+    (tm.tm_hour as usize).min(23)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn entry(
+        song_path: &str,
+        started_at: u64,
+        listened_secs: u64,
+        end: PlaybackEnd,
+    ) -> HistoryEntry {
+        HistoryEntry {
+            song_path: song_path.to_string(),
+            started_at,
+            listened_secs,
+            completed: end == PlaybackEnd::Finished,
+            stopped: end == PlaybackEnd::Stopped,
+        }
+    }
+
+    fn library() -> HashMap<String, Song> {
+        let a = Song {
+            title: Some("Song A".to_string()),
+            artist: Some("Artist".to_string()),
+            album: Some("Album".to_string()),
+            ..Song::untagged("/music/a.mp3")
+        };
+        [a, Song::untagged("/music/b.mp3")]
+            .into_iter()
+            .map(|s| (s.path.to_string_lossy().to_string(), s))
+            .collect()
+    }
+
+    #[test]
+    fn plays_skips_and_tops_are_counted() {
+        let entries = [
+            entry("/music/a.mp3", NOW, 200, PlaybackEnd::Finished),
+            // skipped after half a minute, it was still played
+            entry("/music/a.mp3", NOW, 40, PlaybackEnd::Skipped),
+            entry("/music/b.mp3", NOW, 5, PlaybackEnd::Skipped),
+            entry("/music/gone.mp3", NOW, 10, PlaybackEnd::Stopped),
+        ];
+        let report = ListeningReport::compute(&entries, None, &library());
+        assert_eq!(report.total_listened, Duration::from_secs(255));
+        assert_eq!(report.plays, 2);
+        assert_eq!(report.skips, 2);
+        assert_eq!(report.top_tracks, [("Song A - Artist".to_string(), 2)]);
+        assert_eq!(report.top_artists, [("Artist".to_string(), 2)]);
+        assert_eq!(report.top_albums, [("Album".to_string(), 2)]);
+        assert_eq!(
+            report.most_skipped,
+            [("Song A - Artist".to_string(), 1), ("b.mp3".to_string(), 1)]
+        );
+        assert_eq!(report.minutes_by_hour.iter().sum::<u64>(), 4);
+    }
+
+    #[test]
+    fn stopping_is_not_a_skip() {
+        let entries = [entry("/music/b.mp3", NOW, 5, PlaybackEnd::Stopped)];
+        let report = ListeningReport::compute(&entries, None, &library());
+        assert_eq!(report.skips, 0);
+        assert!(report.most_skipped.is_empty());
+        assert_eq!(report.plays, 0);
+    }
+
+    #[test]
+    fn entries_before_the_range_are_left_out() {
+        let entries = [
+            entry("/music/a.mp3", NOW - 100, 200, PlaybackEnd::Finished),
+            entry("/music/b.mp3", NOW, 60, PlaybackEnd::Finished),
+        ];
+        let report = ListeningReport::compute(&entries, Some(NOW), &library());
+        assert_eq!(report.plays, 1);
+        assert_eq!(report.total_listened, Duration::from_secs(60));
+        assert_eq!(report.top_tracks, [("b.mp3".to_string(), 1)]);
+    }
+
+    #[test]
+    fn songs_missing_from_the_library_show_their_path() {
+        let entries = [entry("/music/gone.mp3", NOW, 100, PlaybackEnd::Finished)];
+        let report = ListeningReport::compute(&entries, None, &library());
+        assert_eq!(report.top_tracks, [("/music/gone.mp3".to_string(), 1)]);
+        assert!(report.top_artists.is_empty());
+    }
+}
@@ -0,0 +1,396 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_width::UnicodeWidthChar;
+
+const HISTORY_CAPACITY: usize = 100;
+
+// a line being typed, used by the edit popup, the search line and the command line
+pub struct TextInput {
+    chars: Vec<char>,
+    cursor: usize, // char index, chars.len() is after the last one
+}
+
+impl TextInput {
+    pub fn new() -> Self {
+        Self {
+            chars: vec![],
+            cursor: 0,
+        }
+    }
+}
+
+impl TextInput {
+    pub fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+    // the cursor goes to the end
+    pub fn set(&mut self, text: &str) {
+        self.chars = text.chars().collect();
+        self.cursor = self.chars.len();
+    }
+    pub fn clear(&mut self) {
+        self.chars.clear();
+        self.cursor = 0;
+    }
+    pub fn insert(&mut self, c: char) {
+        self.chars.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+    // pasted text is a single line, line breaks become spaces
+    pub fn insert_str(&mut self, text: &str) {
+        let text = text.trim_end_matches(['\r', '\n']);
+        for c in text.chars() {
+            match c {
+                '\r' => {}
+                '\n' | '\t' => self.insert(' '),
+                c if c.is_control() => {}
+                c => self.insert(c),
+            }
+        }
+    }
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.chars.remove(self.cursor);
+        }
+    }
+    pub fn delete(&mut self) {
+        if self.cursor < self.chars.len() {
+            self.chars.remove(self.cursor);
+        }
+    }
+    // the word before the cursor and the spaces after it, like a shell
+    pub fn delete_word(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !is_word_break(self.chars[start - 1]) {
+            start -= 1;
+        }
+        // a lone separator goes by itself
+        if start == self.cursor && start > 0 {
+            start -= 1;
+        }
+        self.chars.drain(start..self.cursor);
+        self.cursor = start;
+    }
+    pub fn delete_to_start(&mut self) {
+        self.chars.drain(..self.cursor);
+        self.cursor = 0;
+    }
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.chars.len());
+    }
+    pub fn move_word_left(&mut self) {
+        while self.cursor > 0 && is_word_break(self.chars[self.cursor - 1]) {
+            self.cursor -= 1;
+        }
+        while self.cursor > 0 && !is_word_break(self.chars[self.cursor - 1]) {
+            self.cursor -= 1;
+        }
+    }
+    pub fn move_word_right(&mut self) {
+        let len = self.chars.len();
+        while self.cursor < len && is_word_break(self.chars[self.cursor]) {
+            self.cursor += 1;
+        }
+        while self.cursor < len && !is_word_break(self.chars[self.cursor]) {
+            self.cursor += 1;
+        }
+    }
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+    pub fn move_end(&mut self) {
+        self.cursor = self.chars.len();
+    }
+    // the editing keys, false if the key is left to the caller
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        match (key.code, key.modifiers) {
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => self.insert(c),
+            (KeyCode::Char('w'), KeyModifiers::CONTROL)
+            | (KeyCode::Backspace | KeyCode::Char('h'), KeyModifiers::ALT) => self.delete_word(),
+            (KeyCode::Backspace, _) => self.backspace(),
+            (KeyCode::Delete, _) | (KeyCode::Char('d'), KeyModifiers::CONTROL) => self.delete(),
+            (KeyCode::Char('u'), KeyModifiers::CONTROL) => self.delete_to_start(),
+            (KeyCode::Left, KeyModifiers::CONTROL) | (KeyCode::Char('b'), KeyModifiers::ALT) => {
+                self.move_word_left()
+            }
+            (KeyCode::Right, KeyModifiers::CONTROL) | (KeyCode::Char('f'), KeyModifiers::ALT) => {
+                self.move_word_right()
+            }
+            (KeyCode::Left, _) | (KeyCode::Char('b'), KeyModifiers::CONTROL) => self.move_left(),
+            (KeyCode::Right, _) | (KeyCode::Char('f'), KeyModifiers::CONTROL) => self.move_right(),
+            (KeyCode::Home, _) | (KeyCode::Char('a'), KeyModifiers::CONTROL) => self.move_home(),
+            (KeyCode::End, _) | (KeyCode::Char('e'), KeyModifiers::CONTROL) => self.move_end(),
+            _ => return false,
+        }
+        true
+    }
+    // the part of a one line input that fits in `width` columns with the cursor in view,
+    // and the column of the cursor
+    pub fn view(&self, width: u16) -> (String, u16) {
+        let width = width.max(1) as usize;
+        let mut start = 0;
+        // scroll right until the cursor, and the char under it, fit
+        let cursor_end = (self.cursor + 1).min(self.chars.len());
+        while start < self.cursor && text_width(&self.chars[start..cursor_end]) >= width {
+            start += 1;
+        }
+        let cursor_x = text_width(&self.chars[start..self.cursor]);
+        let mut shown = String::new();
+        let mut shown_width = 0;
+        for c in &self.chars[start..] {
+            shown_width += char_width(*c);
+            if shown_width > width {
+                break;
+            }
+            shown.push(*c);
+        }
+        (shown, cursor_x as u16)
+    }
+    // the input wrapped to `width` columns, and the row and column of the cursor
+    pub fn wrapped(&self, width: u16) -> (Vec<String>, (u16, u16)) {
+        let width = width.max(1) as usize;
+        let mut lines = vec![String::new()];
+        let mut line_width = 0;
+        let mut cursor = (0, 0);
+        for (i, c) in self.chars.iter().enumerate() {
+            let w = char_width(*c);
+            if line_width + w > width {
+                lines.push(String::new());
+                line_width = 0;
+            }
+            if i == self.cursor {
+                cursor = (lines.len() - 1, line_width);
+            }
+            lines.last_mut().unwrap().push(*c);
+            line_width += w;
+        }
+        if self.cursor == self.chars.len() {
+            // a full last line puts the cursor at the start of the next one
+            if line_width >= width {
+                lines.push(String::new());
+                line_width = 0;
+            }
+            cursor = (lines.len() - 1, line_width);
+        }
+        (lines, (cursor.0 as u16, cursor.1 as u16))
+    }
+}
+
+fn is_word_break(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '/' | '\\' | '.' | '-' | '_' | ',' | ':')
+}
+
+fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+fn text_width(chars: &[char]) -> usize {
+    chars.iter().map(|c| char_width(*c)).sum()
+}
+
+// lines entered before, browsed with up and down, newest last
+pub struct InputHistory {
+    entries: Vec<String>,
+    browsing: Option<usize>, // the entry shown
+    draft: String,           // what was typed before browsing started
+}
+
+impl InputHistory {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![],
+            browsing: None,
+            draft: String::new(),
+        }
+    }
+}
+
+impl InputHistory {
+    pub fn push(&mut self, line: &str) {
+        self.browsing = None;
+        if line.trim().is_empty() || self.entries.last().is_some_and(|l| l == line) {
+            return;
+        }
+        if self.entries.len() == HISTORY_CAPACITY {
+            self.entries.remove(0);
+        }
+        self.entries.push(line.to_string());
+    }
+    // forget where the browsing was, for a new input
+    pub fn reset(&mut self) {
+        self.browsing = None;
+    }
+    // older or newer entry into `input`, going past the newest gives back the draft
+    pub fn browse(&mut self, input: &mut TextInput, older: bool) {
+        let idx = match (self.browsing, older) {
+            (None, true) => self.entries.len().checked_sub(1),
+            (None, false) => return,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i + 1 < self.entries.len() => Some(i + 1),
+            (Some(_), false) => None,
+        };
+        if idx.is_none() && self.browsing.is_none() {
+            return;
+        }
+        if self.browsing.is_none() {
+            self.draft = input.text();
+        }
+        self.browsing = idx;
+        match idx {
+            Some(i) => input.set(&self.entries[i]),
+            None => input.set(&self.draft),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(text: &str) -> TextInput {
+        let mut input = TextInput::new();
+        input.set(text);
+        input
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    #[test]
+    fn typing_inserts_at_the_cursor() {
+        let mut input = typed("held");
+        input.move_left();
+        input.move_left();
+        assert!(input.handle_key(KeyEvent::new(KeyCode::Char('l'), KeyModifiers::NONE)));
+        assert_eq!(input.text(), "helld");
+        assert!(input.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE)));
+        assert!(input.handle_key(ctrl('d')));
+        assert_eq!(input.text(), "hed");
+        assert!(input.handle_key(ctrl('a')));
+        input.insert('>');
+        assert_eq!(input.text(), ">hed");
+        assert!(!input.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)));
+    }
+
+    #[test]
+    fn delete_word_stops_at_path_separators() {
+        let mut input = typed("open /music/rock ");
+        input.delete_word();
+        assert_eq!(input.text(), "open /music/");
+        // a lone separator goes by itself
+        input.delete_word();
+        assert_eq!(input.text(), "open /music");
+        input.delete_word();
+        assert_eq!(input.text(), "open /");
+    }
+
+    #[test]
+    fn delete_to_start_keeps_the_text_after_the_cursor() {
+        let mut input = typed("/music/Foo");
+        input.move_word_left();
+        assert!(input.handle_key(ctrl('u')));
+        assert_eq!(input.text(), "Foo");
+    }
+
+    #[test]
+    fn word_moves_skip_separators() {
+        let mut input = typed("a-b c");
+        input.move_home();
+        input.move_word_right();
+        input.insert('|');
+        assert_eq!(input.text(), "a|-b c");
+        input.move_end();
+        input.move_word_left();
+        input.move_word_left();
+        input.insert('|');
+        assert_eq!(input.text(), "a|-|b c");
+    }
+
+    #[test]
+    fn pasted_line_breaks_become_spaces() {
+        let mut input = TextInput::new();
+        input.insert_str("one\r\ntwo\tthree\u{7}\n\n");
+        assert_eq!(input.text(), "one two three");
+    }
+
+    #[test]
+    fn view_scrolls_to_keep_the_cursor_shown() {
+        let input = typed("abcdefgh");
+        assert_eq!(input.view(4), ("fgh".to_string(), 3));
+        let mut input = input;
+        input.move_home();
+        assert_eq!(input.view(4), ("abcd".to_string(), 0));
+    }
+
+    #[test]
+    fn view_counts_wide_chars_as_two_columns() {
+        let input = typed("日本");
+        assert_eq!(input.view(3), ("本".to_string(), 2));
+    }
+
+    #[test]
+    fn a_full_last_line_puts_the_cursor_on_the_next_one() {
+        let input = typed("abcd");
+        assert_eq!(
+            input.wrapped(2),
+            (
+                vec!["ab".to_string(), "cd".to_string(), String::new()],
+                (2, 0)
+            )
+        );
+        let mut input = input;
+        input.move_left();
+        assert_eq!(input.wrapped(2).1, (1, 1));
+    }
+
+    #[test]
+    fn history_skips_blank_lines_and_repeats() {
+        let mut history = InputHistory::new();
+        history.push("one");
+        history.push("  ");
+        history.push("one");
+        history.push("two");
+        assert_eq!(history.entries, ["one", "two"]);
+    }
+
+    #[test]
+    fn history_forgets_the_oldest_line() {
+        let mut history = InputHistory::new();
+        for i in 0..=HISTORY_CAPACITY {
+            history.push(&i.to_string());
+        }
+        assert_eq!(history.entries.len(), HISTORY_CAPACITY);
+        assert_eq!(history.entries[0], "1");
+    }
+
+    #[test]
+    fn browsing_past_the_newest_gives_back_the_draft() {
+        let mut history = InputHistory::new();
+        history.push("one");
+        history.push("two");
+        let mut input = typed("dra");
+        history.browse(&mut input, false);
+        assert_eq!(input.text(), "dra");
+        history.browse(&mut input, true);
+        assert_eq!(input.text(), "two");
+        history.browse(&mut input, true);
+        history.browse(&mut input, true);
+        assert_eq!(input.text(), "one");
+        history.browse(&mut input, false);
+        assert_eq!(input.text(), "two");
+        history.browse(&mut input, false);
+        assert_eq!(input.text(), "dra");
+        history.browse(&mut input, false);
+        assert_eq!(input.text(), "dra");
+    }
+}
@@ -0,0 +1,534 @@
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// what a key does in normal mode, bound to keys in keymap.json by name
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Quit,
+    PreviousTab,
+    NextTab,
+    Open, // focus the songs of the selected playlist
+    Back,
+    SelectNext,
+    SelectPrevious,
+    AddSource,
+    DeleteSource,
+    BrowseFolders,
+    ToggleSource,
+    ToggleRecursive,
+    EditSourceLabel,
+    EditSourceExcludes,
+    Play,
+    Enqueue,
+    NextSong,
+    Stop,
+    CyclePlayMode,
+    Rate(u8),
+    ToggleFavorite,
+    ToggleMark, // mark songs for the bulk actions
+    MarkRange,
+    MarkAll,
+    AddToPlaylist,
+    RemoveFromPlaylist,
+    EditTags,
+    Undo, // playlist, source and tag edits
+    Redo,
+    RecentlyPlayed,
+    FindDuplicates,
+    Confirm,
+    CycleSort,
+    ToggleSortDirection,
+    Search,
+    SearchLibrary,
+    NextMatch,
+    PreviousMatch,
+    CycleStatsRange,
+    CommandLine,
+    ToggleMiniPlayer,
+    Help,
+    MessageLog,
+    DismissMessage, // errors stay on the status line until dismissed
+}
+impl Action {
+    pub const ALL: [Action; 49] = [
+        Action::Quit,
+        Action::PreviousTab,
+        Action::NextTab,
+        Action::Open,
+        Action::Back,
+        Action::SelectNext,
+        Action::SelectPrevious,
+        Action::AddSource,
+        Action::DeleteSource,
+        Action::BrowseFolders,
+        Action::ToggleSource,
+        Action::ToggleRecursive,
+        Action::EditSourceLabel,
+        Action::EditSourceExcludes,
+        Action::Play,
+        Action::Enqueue,
+        Action::NextSong,
+        Action::Stop,
+        Action::CyclePlayMode,
+        Action::Rate(0),
+        Action::Rate(1),
+        Action::Rate(2),
+        Action::Rate(3),
+        Action::Rate(4),
+        Action::Rate(5),
+        Action::ToggleFavorite,
+        Action::ToggleMark,
+        Action::MarkRange,
+        Action::MarkAll,
+        Action::AddToPlaylist,
+        Action::RemoveFromPlaylist,
+        Action::EditTags,
+        Action::Undo,
+        Action::Redo,
+        Action::RecentlyPlayed,
+        Action::FindDuplicates,
+        Action::Confirm,
+        Action::CycleSort,
+        Action::ToggleSortDirection,
+        Action::Search,
+        Action::SearchLibrary,
+        Action::NextMatch,
+        Action::PreviousMatch,
+        Action::CycleStatsRange,
+        Action::CommandLine,
+        Action::ToggleMiniPlayer,
+        Action::Help,
+        Action::MessageLog,
+        Action::DismissMessage,
+    ];
+    // the name used in keymap.json
+    pub fn name(&self) -> String {
+        match self {
+            Action::Quit => "quit".to_string(),
+            Action::PreviousTab => "previous_tab".to_string(),
+            Action::NextTab => "next_tab".to_string(),
+            Action::Open => "open".to_string(),
+            Action::Back => "back".to_string(),
+            Action::SelectNext => "select_next".to_string(),
+            Action::SelectPrevious => "select_previous".to_string(),
+            Action::AddSource => "add_source".to_string(),
+            Action::DeleteSource => "delete_source".to_string(),
+            Action::BrowseFolders => "browse_folders".to_string(),
+            Action::ToggleSource => "toggle_source".to_string(),
+            Action::ToggleRecursive => "toggle_recursive".to_string(),
+            Action::EditSourceLabel => "edit_source_label".to_string(),
+            Action::EditSourceExcludes => "edit_source_excludes".to_string(),
+            Action::Play => "play".to_string(),
+            Action::Enqueue => "enqueue".to_string(),
+            Action::NextSong => "next_song".to_string(),
+            Action::Stop => "stop".to_string(),
+            Action::CyclePlayMode => "cycle_play_mode".to_string(),
+            Action::Rate(rating) => format!("rate_{}", rating),
+            Action::ToggleFavorite => "toggle_favorite".to_string(),
+            Action::ToggleMark => "toggle_mark".to_string(),
+            Action::MarkRange => "mark_range".to_string(),
+            Action::MarkAll => "mark_all".to_string(),
+            Action::AddToPlaylist => "add_to_playlist".to_string(),
+            Action::RemoveFromPlaylist => "remove_from_playlist".to_string(),
+            Action::EditTags => "edit_tags".to_string(),
+            Action::Undo => "undo".to_string(),
+            Action::Redo => "redo".to_string(),
+            Action::RecentlyPlayed => "recently_played".to_string(),
+            Action::FindDuplicates => "find_duplicates".to_string(),
+            Action::Confirm => "confirm".to_string(),
+            Action::CycleSort => "cycle_sort".to_string(),
+            Action::ToggleSortDirection => "toggle_sort_direction".to_string(),
+            Action::Search => "search".to_string(),
+            Action::SearchLibrary => "search_library".to_string(),
+            Action::NextMatch => "next_match".to_string(),
+            Action::PreviousMatch => "previous_match".to_string(),
+            Action::CycleStatsRange => "cycle_stats_range".to_string(),
+            Action::CommandLine => "command_line".to_string(),
+            Action::ToggleMiniPlayer => "toggle_mini_player".to_string(),
+            Action::Help => "help".to_string(),
+            Action::MessageLog => "message_log".to_string(),
+            Action::DismissMessage => "dismiss_message".to_string(),
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|a| a.name() == name)
+    }
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["ctrl-q"],
+            Action::PreviousTab => &["h"],
+            Action::NextTab => &["l"],
+            Action::Open => &["i"],
+            Action::Back => &["b", "backspace"],
+            Action::SelectNext => &["j"],
+            Action::SelectPrevious => &["k"],
+            Action::AddSource => &["a"],
+            Action::DeleteSource => &["d"],
+            Action::BrowseFolders => &["B"],
+            Action::ToggleSource => &["e"],
+            Action::ToggleRecursive => &["R"],
+            Action::EditSourceLabel => &["c"],
+            Action::EditSourceExcludes => &["X"],
+            Action::Play => &["p"],
+            Action::Enqueue => &["q"],
+            Action::NextSong => &[">"],
+            Action::Stop => &["s"],
+            Action::CyclePlayMode => &["m"],
+            Action::Rate(0) => &["0"],
+            Action::Rate(1) => &["1"],
+            Action::Rate(2) => &["2"],
+            Action::Rate(3) => &["3"],
+            Action::Rate(4) => &["4"],
+            Action::Rate(_) => &["5"],
+            Action::ToggleFavorite => &["f"],
+            Action::ToggleMark => &["space"],
+            Action::MarkRange => &["v"],
+            Action::MarkAll => &["A"],
+            Action::AddToPlaylist => &["+"],
+            Action::RemoveFromPlaylist => &["x"],
+            Action::EditTags => &["T"],
+            Action::Undo => &["u"],
+            Action::Redo => &["ctrl-r"],
+            Action::RecentlyPlayed => &["r"],
+            Action::FindDuplicates => &["D"],
+            Action::Confirm => &["enter"],
+            Action::CycleSort => &["o"],
+            Action::ToggleSortDirection => &["O"],
+            Action::Search => &["/"],
+            Action::SearchLibrary => &["ctrl-f"],
+            Action::NextMatch => &["n"],
+            Action::PreviousMatch => &["N"],
+            Action::CycleStatsRange => &["t"],
+            Action::CommandLine => &[":"],
+            Action::ToggleMiniPlayer => &["M"],
+            Action::Help => &["?"],
+            Action::MessageLog => &["L"],
+            Action::DismissMessage => &["esc"],
+        }
+    }
+}
+
+// a key with its modifiers, shift is part of the char for char keys
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyPress {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+impl KeyPress {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        match code {
+            KeyCode::Char(c) => {
+                let c = if modifiers.contains(KeyModifiers::SHIFT) {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                };
+                Self {
+                    code: KeyCode::Char(c),
+                    modifiers: modifiers - KeyModifiers::SHIFT,
+                }
+            }
+            _ => Self { code, modifiers },
+        }
+    }
+    // "j", "D", "ctrl-q", "alt-enter", "f5", "space"
+    fn parse(text: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        loop {
+            if let Some(r) = rest.strip_prefix("ctrl-") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("alt-") {
+                modifiers |= KeyModifiers::ALT;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("shift-") {
+                modifiers |= KeyModifiers::SHIFT;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+        let code = match rest {
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "space" => KeyCode::Char(' '),
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            _ if rest.chars().count() == 1 => KeyCode::Char(rest.chars().next()?),
+            _ => match rest.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                Some(n @ 1..=12) => KeyCode::F(n),
+                _ => return None,
+            },
+        };
+        Some(Self::new(code, modifiers))
+    }
+}
+impl From<KeyEvent> for KeyPress {
+    fn from(key: KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+}
+impl fmt::Display for KeyPress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "shift-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::Enter => write!(f, "enter"),
+            KeyCode::Esc => write!(f, "esc"),
+            KeyCode::Tab => write!(f, "tab"),
+            KeyCode::BackTab => write!(f, "backtab"),
+            KeyCode::Backspace => write!(f, "backspace"),
+            KeyCode::Delete => write!(f, "delete"),
+            KeyCode::Insert => write!(f, "insert"),
+            KeyCode::Up => write!(f, "up"),
+            KeyCode::Down => write!(f, "down"),
+            KeyCode::Left => write!(f, "left"),
+            KeyCode::Right => write!(f, "right"),
+            KeyCode::Home => write!(f, "home"),
+            KeyCode::End => write!(f, "end"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            KeyCode::Null => write!(f, "null"),
+        }
+    }
+}
+
+fn format_sequence(sequence: &[KeyPress]) -> String {
+    sequence
+        .iter()
+        .map(|k| k.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// normal mode keys, keymap.json maps action names to key sequences:
+// { "select_next": ["j", "down"], "quit": ["ctrl-q", "g q"] }
+// actions missing from the file keep their default keys
+pub struct Keymap {
+    bindings: Vec<(Vec<KeyPress>, Action)>,
+    pending: Vec<KeyPress>, // keys typed so far of a multi-key sequence
+}
+impl Keymap {
+    // also returns the problems found in keymap.json and the conflicting bindings
+    pub fn load(file_path: &Path) -> (Self, Vec<String>) {
+        let mut problems = vec![];
+        let mut user_keys: HashMap<String, Vec<String>> = HashMap::new();
+        if let Ok(data) = fs::read_to_string(file_path) {
+            match serde_json::from_str(&data) {
+                Ok(keys) => user_keys = keys,
+                Err(e) => problems.push(format!("{}: {}", file_path.display(), e)),
+            }
+        }
+        for name in user_keys.keys() {
+            if Action::from_name(name).is_none() {
+                problems.push(format!("unknown action \"{}\"", name));
+            }
+        }
+        let mut bindings = vec![];
+        for action in Action::ALL {
+            let keys: Vec<String> = match user_keys.get(&action.name()) {
+                Some(keys) => keys.clone(),
+                None => action
+                    .default_keys()
+                    .iter()
+                    .map(|k| k.to_string())
+                    .collect(),
+            };
+            for key in keys {
+                let sequence: Option<Vec<KeyPress>> =
+                    key.split_whitespace().map(KeyPress::parse).collect();
+                match sequence {
+                    Some(sequence) if !sequence.is_empty() => bindings.push((sequence, action)),
+                    _ => problems.push(format!(
+                        "invalid key \"{}\" for action \"{}\"",
+                        key,
+                        action.name()
+                    )),
+                }
+            }
+        }
+        let keymap = Self {
+            bindings,
+            pending: vec![],
+        };
+        problems.extend(keymap.conflicts());
+        (keymap, problems)
+    }
+}
+impl Keymap {
+    // a sequence bound twice, or the prefix of another sequence, can never be told apart
+    fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = vec![];
+        for (i, (a_keys, a_action)) in self.bindings.iter().enumerate() {
+            for (b_keys, b_action) in self.bindings.iter().skip(i + 1) {
+                if a_keys == b_keys {
+                    if a_action != b_action {
+                        conflicts.push(format!(
+                            "\"{}\" is bound to both \"{}\" and \"{}\"",
+                            format_sequence(a_keys),
+                            a_action.name(),
+                            b_action.name()
+                        ));
+                    }
+                } else if a_keys.starts_with(b_keys) || b_keys.starts_with(a_keys) {
+                    let (short, short_action, long, long_action) = if a_keys.len() < b_keys.len() {
+                        (a_keys, a_action, b_keys, b_action)
+                    } else {
+                        (b_keys, b_action, a_keys, a_action)
+                    };
+                    conflicts.push(format!(
+                        "\"{}\" ({}) hides \"{}\" ({})",
+                        format_sequence(short),
+                        short_action.name(),
+                        format_sequence(long),
+                        long_action.name()
+                    ));
+                }
+            }
+        }
+        conflicts
+    }
+    // the action of a complete sequence, None while a sequence is being typed or unbound
+    pub fn feed(&mut self, key: KeyEvent) -> Option<Action> {
+        self.pending.push(KeyPress::from(key));
+        let mut is_prefix = false;
+        for (keys, action) in self.bindings.iter() {
+            if *keys == self.pending {
+                self.pending.clear();
+                return Some(*action);
+            }
+            is_prefix |= keys.starts_with(&self.pending);
+        }
+        if !is_prefix {
+            // a wrong key ends the sequence, it may start a new one by itself
+            let started_sequence = self.pending.len() > 1;
+            self.pending.clear();
+            if started_sequence {
+                return self.feed(key);
+            }
+        }
+        None
+    }
+    // keys bound to the action as written in keymap.json, for hints
+    pub fn keys_of(&self, action: Action) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(keys, _)| format_sequence(keys))
+            .collect()
+    }
+    pub fn hint(&self, action: Action) -> String {
+        self.keys_of(action)
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| "-".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keymap(bindings: &[(&str, Action)]) -> Keymap {
+        Keymap {
+            bindings: bindings
+                .iter()
+                .map(|(keys, action)| {
+                    let sequence: Option<Vec<KeyPress>> =
+                        keys.split_whitespace().map(KeyPress::parse).collect();
+                    (sequence.unwrap(), *action)
+                })
+                .collect(),
+            pending: vec![],
+        }
+    }
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn default_keys_do_not_conflict() {
+        let (keymap, problems) = Keymap::load(Path::new("/nonexistent/keymap.json"));
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(keymap.keys_of(Action::Quit), ["ctrl-q"]);
+    }
+
+    #[test]
+    fn a_sequence_bound_twice_conflicts() {
+        let keymap = keymap(&[("j", Action::SelectNext), ("j", Action::Play)]);
+        assert_eq!(
+            keymap.conflicts(),
+            ["\"j\" is bound to both \"select_next\" and \"play\""]
+        );
+    }
+
+    #[test]
+    fn a_sequence_bound_twice_to_the_same_action_does_not_conflict() {
+        let keymap = keymap(&[("j", Action::SelectNext), ("j", Action::SelectNext)]);
+        assert!(keymap.conflicts().is_empty());
+    }
+
+    #[test]
+    fn a_prefix_hides_the_longer_sequence() {
+        let keymap = keymap(&[("g q", Action::Quit), ("g", Action::Search)]);
+        assert_eq!(keymap.conflicts(), ["\"g\" (search) hides \"g q\" (quit)"]);
+    }
+
+    #[test]
+    fn feed_waits_for_the_whole_sequence() {
+        let mut keymap = keymap(&[("g q", Action::Quit), ("j", Action::SelectNext)]);
+        assert_eq!(keymap.feed(key('j')), Some(Action::SelectNext));
+        assert_eq!(keymap.feed(key('g')), None);
+        assert_eq!(keymap.feed(key('q')), Some(Action::Quit));
+    }
+
+    #[test]
+    fn a_wrong_key_ends_the_sequence_and_starts_its_own() {
+        let mut keymap = keymap(&[("g q", Action::Quit), ("j", Action::SelectNext)]);
+        assert_eq!(keymap.feed(key('g')), None);
+        assert_eq!(keymap.feed(key('j')), Some(Action::SelectNext));
+        // the sequence starts over
+        assert_eq!(keymap.feed(key('q')), None);
+        assert_eq!(keymap.feed(key('g')), None);
+        assert_eq!(keymap.feed(key('q')), Some(Action::Quit));
+    }
+
+    #[test]
+    fn feed_matches_shifted_chars_and_modifiers() {
+        let mut keymap = keymap(&[
+            ("D", Action::FindDuplicates),
+            ("ctrl-f", Action::SearchLibrary),
+        ]);
+        let shifted = KeyEvent::new(KeyCode::Char('d'), KeyModifiers::SHIFT);
+        assert_eq!(keymap.feed(shifted), Some(Action::FindDuplicates));
+        let upper = KeyEvent::new(KeyCode::Char('D'), KeyModifiers::SHIFT);
+        assert_eq!(keymap.feed(upper), Some(Action::FindDuplicates));
+        assert_eq!(keymap.feed(key('f')), None);
+        let ctrl_f = KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.feed(ctrl_f), Some(Action::SearchLibrary));
+    }
+}
@@ -1,4 +1,24 @@
-use rodio::{source, Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+mod browser;
+mod columns;
+mod command;
+mod duplicates;
+mod history;
+mod input;
+mod keymap;
+mod mouse;
+mod notify;
+mod paths;
+mod screen;
+mod search;
+mod settings;
+mod sources;
+mod stats;
+mod tags;
+mod term;
+mod theme;
+mod undo;
+
+use rodio::{Decoder, OutputStream, Sink, Source as _};
 //todo: add home tabstate and scan audio files from sources folder
 //2. middle: songs list
 //3. right: song info
@@ -6,71 +26,137 @@ use rodio::{source, Decoder, OutputStream, OutputStreamHandle, Sink, Source};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::{
-    cell::Ref,
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{hash_map::RandomState, HashMap, HashSet, VecDeque},
     env, ffi,
     fs::{self, File},
-    io::{self, BufReader, Write},
-    os,
+    hash::{BuildHasher, Hasher},
+    io::{self, BufReader},
+    mem,
+    ops::RangeInclusive,
     path::{Path, PathBuf},
     rc::Rc,
-    str::FromStr,
     sync::{
-        mpsc::{self, Receiver, Sender},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
         Arc, Mutex,
     },
     thread,
-    time::Duration,
-    usize,
+    time::{Duration, Instant},
 };
 
-use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen},
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
 };
 use tui::{
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
-    style::{Color, Modifier, Style},
+    style::Style,
     symbols::{self, DOT},
-    text::{Span, Spans, Text},
-    widgets::{Block, Borders, LineGauge, List, ListItem, ListState, Paragraph, Tabs, Wrap},
+    text::{Span, Spans},
+    widgets::{
+        BarChart, Block, Borders, Cell, Clear, LineGauge, List, ListItem, ListState, Paragraph,
+        Row, Table, TableState, Tabs, Wrap,
+    },
     Terminal,
 };
 
+use browser::FolderBrowser;
+use columns::{SongColumn, SongSort};
+use command::Command;
+use duplicates::DuplicatesView;
+use history::{HistoryEntry, ListenClock, ListeningHistory, ListeningReport, PlaybackEnd};
+use id3::TagLike;
+use input::{InputHistory, TextInput};
+use keymap::{Action, Keymap};
+use mouse::{ListKind, MouseMap, MouseTarget};
+use notify::{Level, Notifications};
+use screen::ScreenBoards;
+use search::{Search, SearchHit, SearchScope};
+use settings::{SettingItem, SettingKind, Settings};
+use sources::{Source, SourceDB, SourceRemoval};
+use stats::{SmartPlaylist, SongStatsDB};
+use tags::{TagChange, TagField};
+use term::TerminalGuard;
+use theme::Theme;
+use undo::{Edit, PlaylistContent, Snapshot, UndoHistory};
+
 #[derive(Debug)]
 enum InputEvent<I> {
     Input(I),
+    Mouse(MouseEvent),
+    Paste(String), // text that came in faster than it can be typed
     Tick,
 }
 
 enum HomeTabStateFocus {
     Left,
     Mid,
-    Right,
+    Duplicates,
+    RecentlyPlayed,
+}
+// what the edit popup of the home tab was opened for
+#[derive(Clone, Copy)]
+enum SongEdit {
+    AddToPlaylist,
+    Tags,
 }
 //home tab state
 struct HomeTabState {
     configuration: Rc<Configuration>,
+    song_stats: Arc<Mutex<SongStatsDB>>,
+    song_stats_revision: u64, // revision the smart playlists were built from
+    listening_history: Arc<Mutex<ListeningHistory>>,
+    recently_played: Vec<(HistoryEntry, Option<Song>)>, // newest first, no song if the file is gone
+    recently_played_state: ListState,
+    listening_history_revision: u64, // revision recently_played was built from
     playlists: Vec<PlayList>,
     playlists_state: ListState,
-    songs_list_states: Vec<ListState>, // each liststate coressponding to each playlist liststate
+    songs_list_states: Vec<TableState>, // each liststate coressponding to each playlist liststate
+    song_columns: Vec<SongColumn>,
     focus: HomeTabStateFocus,
+    duplicates_view: Option<DuplicatesView>,
+    // dropped duplicate path -> kept path, dropped copies are skipped when loading
+    duplicate_replacements: HashMap<String, String>,
+    broken_songs: HashSet<PathBuf>, // failed to open or decode this session, drawn in red
+    marked_songs: HashSet<PathBuf>, // of the open playlist, the bulk actions apply to them
+    mark_anchor: Option<usize>,     // where the range being marked starts
+    editing: SongEdit,
+    playlists_revision: u64, // bumped on every save, the undo history looks for edits then
+    // the values of the tags written since the undo history last looked
+    tag_undo: Vec<(PathBuf, Vec<TagChange>)>,
 }
 impl HomeTabState {
-    fn new(configuration: Rc<Configuration>) -> Self {
+    fn new(
+        configuration: Rc<Configuration>,
+        song_stats: Arc<Mutex<SongStatsDB>>,
+        listening_history: Arc<Mutex<ListeningHistory>>,
+    ) -> Self {
         Self {
             configuration,
+            song_stats,
+            song_stats_revision: 0,
+            listening_history,
+            recently_played: Vec::new(),
+            recently_played_state: ListState::default(),
+            listening_history_revision: 0,
             playlists: Vec::new(),
             playlists_state: ListState::default(),
             songs_list_states: Vec::new(),
+            song_columns: SongColumn::default_columns(),
             focus: HomeTabStateFocus::Left,
+            duplicates_view: None,
+            duplicate_replacements: HashMap::new(),
+            broken_songs: HashSet::new(),
+            marked_songs: HashSet::new(),
+            mark_anchor: None,
+            editing: SongEdit::AddToPlaylist,
+            playlists_revision: 0,
+            tag_undo: vec![],
         }
     }
 }
 impl HomeTabState {
-    fn load_data(&mut self, source_folder_paths: Vec<PathBuf>) {
+    // the tracks and bytes found in each source, None for the ones not scanned
+    fn load_data(&mut self, sources: &[Source], settings: &Settings) -> Vec<Option<(usize, u64)>> {
         let data = fs::read_to_string(&self.configuration.playlist_file_path).unwrap();
         let raw_json_data: Value = serde_json::from_str(&data).unwrap_or_else(|_| {
             json!({
@@ -83,19 +169,35 @@ impl HomeTabState {
             serde_json::from_value(raw_json_data["playlist_songpaths_relations"].clone())
                 .unwrap_or_else(|_| HashMap::new());
 
+        //load sort order of each playlist
+        let playlist_sort_orders: HashMap<String, SongSort> =
+            serde_json::from_value(raw_json_data["playlist_sort_orders"].clone())
+                .unwrap_or_else(|_| HashMap::new());
+
+        //load which copy of a duplicated song is kept
+        self.duplicate_replacements =
+            serde_json::from_value(raw_json_data["duplicate_replacements"].clone())
+                .unwrap_or_else(|_| HashMap::new());
+
         //load all data to playlists
         let mut songs_set: HashSet<Song> = HashSet::new();
-        const DEFAULT_PLAYLIST_NAME: &str = "Default";
         let mut default_playlist = PlayList {
             name: DEFAULT_PLAYLIST_NAME.to_string(),
             songs: vec![],
+            sort: None,
+            smart: None,
         };
         for (playlist_name, song_paths) in playlist_songpaths_relations {
             let mut playlist = PlayList {
                 name: playlist_name.clone(),
                 songs: vec![],
+                sort: None,
+                smart: None,
             };
             for song_path in song_paths.iter() {
+                if self.duplicate_replacements.contains_key(song_path) {
+                    continue;
+                }
                 let song = Song::new(PathBuf::from(song_path));
                 if let Some(song) = song {
                     playlist.songs.push(song.clone());
@@ -113,53 +215,125 @@ impl HomeTabState {
         //1. make a set containing current all current songs
         //2. scan all files in source folder
         //3. if the song is not in the set, add the song to default playlist
-        for source_folder_path in source_folder_paths {
-            let read_dir = fs::read_dir(&source_folder_path);
-            if let Ok(read_dir) = read_dir {
-                for dir_entry in read_dir {
-                    let dir_entry_path = dir_entry.unwrap().path();
-                    if dir_entry_path.is_file() {
-                        let extention = dir_entry_path
-                            .extension()
-                            .unwrap_or_default()
-                            .to_str()
-                            .unwrap_or_default();
-                        match extention {
-                            "mp3" | "wav" => {
-                                let song = Song::new(dir_entry_path);
-                                if let Some(song) = song {
-                                    if !songs_set.contains(&song) {
-                                        default_playlist.songs.push(song.clone());
-                                        songs_set.insert(song);
-                                    }
-                                }
-                            }
-                            _ => {}
-                        }
+        let mut scan_results = vec![];
+        for source in sources {
+            // an unreachable source keeps what its last scan found
+            if !source.enabled || !source.is_reachable() {
+                scan_results.push(None);
+                continue;
+            }
+            let song_paths = source.scan(settings.scan_recursive, &settings.scan_extensions);
+            let (mut tracks, mut size) = (0, 0);
+            for song_path in song_paths {
+                if self
+                    .duplicate_replacements
+                    .contains_key(song_path.to_string_lossy().as_ref())
+                {
+                    continue;
+                }
+                if let Some(song) = Song::new(song_path) {
+                    tracks += 1;
+                    size += song.size;
+                    if !songs_set.contains(&song) {
+                        default_playlist.songs.push(song.clone());
+                        songs_set.insert(song);
                     }
                 }
             }
+            scan_results.push(Some((tracks, size)));
         }
         self.playlists.push(default_playlist);
+        //smart playlists are filled from song stats after loading
+        for smart in SmartPlaylist::ALL {
+            self.playlists.push(PlayList {
+                name: smart.name().to_string(),
+                songs: vec![],
+                sort: None,
+                smart: Some(smart),
+            });
+        }
+        let song_stats = self.song_stats.lock().unwrap();
+        for playlist in self.playlists.iter_mut() {
+            playlist.sort = playlist_sort_orders.get(&playlist.name).copied();
+            playlist.sort_songs(&song_stats);
+        }
+        drop(song_stats);
 
-        //sort playlist in a order but guarantee default playlist is the first
-        self.playlists.sort_by(|a, b| {
-            if a.name == DEFAULT_PLAYLIST_NAME {
-                std::cmp::Ordering::Less
-            } else if b.name == DEFAULT_PLAYLIST_NAME {
-                std::cmp::Ordering::Greater
-            } else {
-                a.name.cmp(&b.name)
-            }
-        });
+        //sort playlist in a order but guarantee default playlist is the first,
+        //followed by the smart playlists
+        let rank = |p: &PlayList| match p.smart {
+            _ if p.name == DEFAULT_PLAYLIST_NAME => 0,
+            Some(smart) => 1 + SmartPlaylist::ALL.iter().position(|s| *s == smart).unwrap(),
+            None => 1 + SmartPlaylist::ALL.len(),
+        };
+        self.playlists
+            .sort_by(|a, b| rank(a).cmp(&rank(b)).then_with(|| a.name.cmp(&b.name)));
         self.playlists_state.select(Some(0));
 
         //init songs_states for each playlist
         self.songs_list_states = self
             .playlists
             .iter()
-            .map(|_| ListState::default())
+            .map(|_| TableState::default())
             .collect();
+        self.refresh_smart_playlists();
+
+        //columns the songs table shows
+        if !settings.song_columns.is_empty() {
+            self.song_columns = settings.song_columns.clone();
+        }
+        scan_results
+    }
+    // scan the sources again, keeping the selected playlist if it is still there
+    fn rescan(&mut self, sources: &[Source], settings: &Settings) -> Vec<Option<(usize, u64)>> {
+        let selected_name = self
+            .playlists_state
+            .selected()
+            .map(|i| self.playlists[i].name.clone());
+        self.playlists.clear();
+        self.songs_list_states.clear();
+        self.focus = HomeTabStateFocus::Left;
+        self.duplicates_view = None;
+        self.clear_marks();
+        let scan_results = self.load_data(sources, settings);
+        let selected_idx =
+            selected_name.and_then(|name| self.playlists.iter().position(|p| p.name == name));
+        if let Some(selected_idx) = selected_idx {
+            self.playlists_state.select(Some(selected_idx));
+        }
+        scan_results
+    }
+    fn create_playlist(&mut self, name: String) -> Result<(), String> {
+        let idx = self.insert_playlist(name)?;
+        self.select_playlist(idx);
+        self.save_playlists()
+            .map_err(|e| format!("Cannot save playlists: {}", e))
+    }
+    // an empty playlist, the selection stays on the selected playlist, not saved
+    fn insert_playlist(&mut self, name: String) -> Result<usize, String> {
+        if self.playlists.iter().any(|p| p.name == name) {
+            return Err(format!("playlist {} already exists", name));
+        }
+        // after the default and smart playlists, in name order
+        let first_idx = (1 + SmartPlaylist::ALL.len()).min(self.playlists.len());
+        let idx = self.playlists[first_idx..]
+            .iter()
+            .position(|p| p.name > name)
+            .map_or(self.playlists.len(), |i| first_idx + i);
+        self.playlists.insert(
+            idx,
+            PlayList {
+                name,
+                songs: vec![],
+                sort: None,
+                smart: None,
+            },
+        );
+        self.songs_list_states.insert(idx, TableState::default());
+        if let Some(selected) = self.playlists_state.selected().filter(|i| *i >= idx) {
+            self.playlists_state.select(Some(selected + 1));
+        }
+        Ok(idx)
     }
     fn select_next_playlist(&mut self) {
         let i = match self.playlists_state.selected() {
@@ -172,6 +346,7 @@ impl HomeTabState {
             }
             None => 0,
         };
+        self.clear_marks();
         self.playlists_state.select(Some(i));
     }
     fn select_previous_playlist(&mut self) {
@@ -185,6 +360,7 @@ impl HomeTabState {
             }
             None => 0,
         };
+        self.clear_marks();
         self.playlists_state.select(Some(i));
     }
     fn enter_current_playlist_songs_list(&mut self) {
@@ -234,6 +410,374 @@ impl HomeTabState {
             current_songs_list_state.select(Some(i));
         }
     }
+    // focus the playlists list with the playlist at `playlist_idx` selected
+    fn select_playlist(&mut self, playlist_idx: usize) {
+        if playlist_idx >= self.playlists.len() {
+            return;
+        }
+        if self.playlists_state.selected() != Some(playlist_idx) {
+            self.clear_marks();
+        }
+        self.back_to_playlists_list();
+        self.playlists_state.select(Some(playlist_idx));
+    }
+    // focus the songs list of the playlist at `playlist_idx` with the song at `song_idx` selected
+    fn select_song(&mut self, playlist_idx: usize, song_idx: usize) {
+        if playlist_idx >= self.playlists.len()
+            || song_idx >= self.playlists[playlist_idx].songs.len()
+        {
+            return;
+        }
+        if self.playlists_state.selected() != Some(playlist_idx) {
+            self.clear_marks();
+        }
+        self.back_to_playlists_list();
+        self.playlists_state.select(Some(playlist_idx));
+        self.focus = HomeTabStateFocus::Mid;
+        self.songs_list_states[playlist_idx].select(Some(song_idx));
+    }
+    // 'o': sort the current playlist by the next column, 'O': flip the direction
+    fn cycle_sort_column(&mut self) -> io::Result<()> {
+        if let Some(idx) = self.playlists_state.selected() {
+            let columns = &self.song_columns;
+            let column = match self.playlists[idx].sort {
+                Some(sort) => {
+                    let i = columns.iter().position(|c| *c == sort.column);
+                    columns[i.map_or(0, |i| (i + 1) % columns.len())]
+                }
+                None => columns[0],
+            };
+            self.sort_playlist(
+                idx,
+                SongSort {
+                    column,
+                    ascending: true,
+                },
+            )?;
+        }
+        Ok(())
+    }
+    fn toggle_sort_direction(&mut self) -> io::Result<()> {
+        if let Some(idx) = self.playlists_state.selected() {
+            let sort = self.playlists[idx].sort.unwrap_or(SongSort {
+                column: self.song_columns[0],
+                ascending: true,
+            });
+            self.sort_playlist(
+                idx,
+                SongSort {
+                    column: sort.column,
+                    ascending: !sort.ascending,
+                },
+            )?;
+        }
+        Ok(())
+    }
+    fn sort_playlist(&mut self, playlist_idx: usize, sort: SongSort) -> io::Result<()> {
+        self.playlists[playlist_idx].sort = Some(sort);
+        self.resort_playlist(playlist_idx);
+        // the range was between rows that moved
+        self.mark_anchor = None;
+        self.save_playlists()
+    }
+    // sort the songs again by the sort they have, not saved
+    fn resort_playlist(&mut self, playlist_idx: usize) {
+        let playlist = &mut self.playlists[playlist_idx];
+        let songs_list_state = &mut self.songs_list_states[playlist_idx];
+        // keep the selected song selected
+        let selected_path = songs_list_state
+            .selected()
+            .map(|i| playlist.songs[i].path.clone());
+        playlist.sort_songs(&self.song_stats.lock().unwrap());
+        if let Some(selected_path) = selected_path {
+            songs_list_state.select(playlist.songs.iter().position(|s| s.path == selected_path));
+        }
+    }
+    // write playlists, their sort orders and the kept duplicates back to playlist.json
+    fn save_playlists(&mut self) -> io::Result<()> {
+        self.playlists_revision += 1;
+        let playlist_songpaths_relations: HashMap<&String, Vec<String>> = self
+            .playlists
+            .iter()
+            .filter(|p| p.smart.is_none())
+            .map(|p| {
+                let song_paths = p
+                    .songs
+                    .iter()
+                    .map(|s| s.path.to_string_lossy().to_string())
+                    .collect();
+                (&p.name, song_paths)
+            })
+            .collect();
+        let playlist_sort_orders: HashMap<&String, SongSort> = self
+            .playlists
+            .iter()
+            .filter_map(|p| p.sort.map(|sort| (&p.name, sort)))
+            .collect();
+        let raw_json_data = json!({
+            "playlist_songpaths_relations": playlist_songpaths_relations,
+            "playlist_sort_orders": playlist_sort_orders,
+            "duplicate_replacements": self.duplicate_replacements,
+        });
+        fs::write(
+            &self.configuration.playlist_file_path,
+            serde_json::to_string_pretty(&raw_json_data).unwrap(),
+        )
+    }
+    fn open_duplicates_view(&mut self) {
+        // each song once, a song may be in several playlists
+        let mut songs_set: HashSet<&PathBuf> = HashSet::new();
+        let songs: Vec<Song> = self
+            .playlists
+            .iter()
+            .flat_map(|p| p.songs.iter())
+            .filter(|s| songs_set.insert(&s.path))
+            .cloned()
+            .collect();
+        self.duplicates_view = Some(DuplicatesView::new(duplicates::find_duplicates(&songs)));
+        self.focus = HomeTabStateFocus::Duplicates;
+    }
+    fn close_duplicates_view(&mut self) {
+        self.duplicates_view = None;
+        self.back_to_playlists_list();
+    }
+    // replace the other copies of the selected duplicate with it in every playlist
+    // the kept copy and how many copies it replaced
+    fn keep_selected_duplicate(&mut self) -> io::Result<Option<(Song, usize)>> {
+        let (kept, dropped) = match self
+            .duplicates_view
+            .as_ref()
+            .and_then(|v| v.selected_copy())
+        {
+            Some(selected) => selected,
+            None => return Ok(None),
+        };
+        for song in dropped.iter() {
+            self.duplicate_replacements.insert(
+                song.path.to_string_lossy().to_string(),
+                kept.path.to_string_lossy().to_string(),
+            );
+        }
+        for playlist in self.playlists.iter_mut() {
+            let mut has_kept = false;
+            let mut songs = vec![];
+            for song in playlist.songs.drain(..) {
+                let song = if dropped.iter().any(|d| d.path == song.path) {
+                    kept.clone()
+                } else {
+                    song
+                };
+                if song.path == kept.path {
+                    if has_kept {
+                        continue;
+                    }
+                    has_kept = true;
+                }
+                songs.push(song);
+            }
+            playlist.songs = songs;
+        }
+        self.clamp_song_selections();
+        self.refresh_smart_playlists();
+        if let Some(view) = self.duplicates_view.as_mut() {
+            view.remove_selected_group();
+        }
+        self.save_playlists()?;
+        Ok(Some((kept, dropped.len())))
+    }
+    // after songs were taken out of the playlists
+    fn clamp_song_selections(&mut self) {
+        self.mark_anchor = None;
+        for (playlist, songs_list_state) in
+            self.playlists.iter().zip(self.songs_list_states.iter_mut())
+        {
+            if let Some(selected) = songs_list_state.selected() {
+                if selected >= playlist.songs.len() {
+                    songs_list_state.select(playlist.songs.len().checked_sub(1));
+                }
+            }
+        }
+    }
+    // what the removal of a source takes from the default playlist and from the other playlists
+    fn count_source_removal(&self, removal: &mut SourceRemoval) {
+        for playlist in self.playlists.iter().filter(|p| p.smart.is_none()) {
+            let taken = playlist
+                .songs
+                .iter()
+                .filter(|s| removal.takes(&s.path))
+                .count();
+            if playlist.name == DEFAULT_PLAYLIST_NAME {
+                removal.library_tracks += taken;
+            } else if taken > 0 {
+                removal.playlist_entries += taken;
+                removal.playlists += 1;
+            }
+        }
+    }
+    // the songs of a removed source leave the default playlist, and the other playlists if pruned
+    fn remove_source_songs(&mut self, removal: &SourceRemoval, prune: bool) -> io::Result<()> {
+        for playlist in self.playlists.iter_mut().filter(|p| p.smart.is_none()) {
+            if prune || playlist.name == DEFAULT_PLAYLIST_NAME {
+                playlist.songs.retain(|s| !removal.takes(&s.path));
+            }
+        }
+        // its groups may hold songs that are gone
+        if self.duplicates_view.is_some() {
+            self.close_duplicates_view();
+        }
+        self.clamp_song_selections();
+        self.refresh_smart_playlists();
+        self.save_playlists()
+    }
+    // the library songs by path, to look up the songs of history entries
+    fn songs_by_path(&self) -> HashMap<String, Song> {
+        self.playlists
+            .iter()
+            .filter(|p| p.smart.is_none())
+            .flat_map(|p| p.songs.iter())
+            .map(|s| (s.path.to_string_lossy().to_string(), s.clone()))
+            .collect()
+    }
+    // songs of all playlists that are not smart
+    fn library(&self) -> Vec<Song> {
+        self.playlists
+            .iter()
+            .filter(|p| p.smart.is_none())
+            .flat_map(|p| p.songs.iter().cloned())
+            .collect()
+    }
+    // rebuild the smart playlists from the songs of the other playlists
+    fn refresh_smart_playlists(&mut self) {
+        let library = self.library();
+        let song_stats = self.song_stats.lock().unwrap();
+        for (playlist, songs_list_state) in self
+            .playlists
+            .iter_mut()
+            .zip(self.songs_list_states.iter_mut())
+        {
+            if let Some(smart) = playlist.smart {
+                playlist.songs = smart.select(&library, &song_stats);
+                playlist.sort_songs(&song_stats);
+                if let Some(selected) = songs_list_state.selected() {
+                    if selected >= playlist.songs.len() {
+                        songs_list_state.select(playlist.songs.len().checked_sub(1));
+                    }
+                }
+            }
+        }
+        self.song_stats_revision = song_stats.revision;
+    }
+    // the playback thread updates play counts, refresh when they changed
+    fn refresh_smart_playlists_if_stats_changed(&mut self) {
+        let revision = self.song_stats.lock().unwrap().revision;
+        if revision != self.song_stats_revision {
+            self.refresh_smart_playlists();
+        }
+    }
+    fn open_recently_played(&mut self) {
+        self.load_recently_played();
+        self.focus = HomeTabStateFocus::RecentlyPlayed;
+        self.recently_played_state
+            .select(if self.recently_played.is_empty() {
+                None
+            } else {
+                Some(0)
+            });
+    }
+    fn close_recently_played(&mut self) {
+        self.recently_played.clear();
+        self.back_to_playlists_list();
+    }
+    fn load_recently_played(&mut self) {
+        const RECENTLY_PLAYED_LIMIT: usize = 200;
+        let library = self.songs_by_path();
+        let listening_history = self.listening_history.lock().unwrap();
+        self.recently_played = listening_history
+            .entries
+            .iter()
+            .rev()
+            .take(RECENTLY_PLAYED_LIMIT)
+            .map(|entry| {
+                // files played from the folder browser are not in the library
+                let song = library.get(&entry.song_path).cloned().or_else(|| {
+                    let path = PathBuf::from(&entry.song_path);
+                    path.is_file().then(|| Song::new(path)).flatten()
+                });
+                (entry.clone(), song)
+            })
+            .collect();
+        self.listening_history_revision = listening_history.revision;
+    }
+    // a song finished while the view is open, show it on top
+    fn refresh_recently_played_if_history_changed(&mut self) {
+        if let HomeTabStateFocus::RecentlyPlayed = self.focus {
+            let revision = self.listening_history.lock().unwrap().revision;
+            if revision != self.listening_history_revision {
+                // each appended entry bumps the revision, keep the same entry selected
+                let added = (revision - self.listening_history_revision) as usize;
+                self.load_recently_played();
+                let selected = self
+                    .recently_played_state
+                    .selected()
+                    .map_or(0, |i| i + added);
+                self.recently_played_state
+                    .select(Some(selected.min(self.recently_played.len() - 1)));
+            }
+        }
+    }
+    fn select_next_recently_played(&mut self) {
+        if let Some(i) = self.recently_played_state.selected() {
+            self.recently_played_state
+                .select(Some((i + 1) % self.recently_played.len()));
+        }
+    }
+    fn select_previous_recently_played(&mut self) {
+        if let Some(i) = self.recently_played_state.selected() {
+            self.recently_played_state.select(Some(if i == 0 {
+                self.recently_played.len() - 1
+            } else {
+                i - 1
+            }));
+        }
+    }
+    fn clone_current_selected_recently_played_song(&self) -> Option<Song> {
+        let i = self.recently_played_state.selected()?;
+        self.recently_played.get(i)?.1.clone()
+    }
+    fn rate_target_songs(&mut self, rating: u8) -> io::Result<usize> {
+        let paths: Vec<PathBuf> = self
+            .clone_target_songs()
+            .into_iter()
+            .map(|s| s.path)
+            .collect();
+        if !paths.is_empty() {
+            self.song_stats
+                .lock()
+                .unwrap()
+                .set_ratings(&paths, rating)?;
+        }
+        Ok(paths.len())
+    }
+    // they all become favorites, unless they all are already
+    fn toggle_target_songs_favorite(&mut self) -> io::Result<(usize, bool)> {
+        let paths: Vec<PathBuf> = self
+            .clone_target_songs()
+            .into_iter()
+            .map(|s| s.path)
+            .collect();
+        let mut song_stats = self.song_stats.lock().unwrap();
+        let favorite = !paths.iter().all(|p| song_stats.get(p).favorite);
+        if !paths.is_empty() {
+            song_stats.set_favorites(&paths, favorite)?;
+        }
+        Ok((paths.len(), favorite))
+    }
+    fn clone_current_playlist_songs(&self) -> Vec<Song> {
+        self.playlists_state
+            .selected()
+            .map(|i| self.playlists[i].songs.clone())
+            .unwrap_or_default()
+    }
     fn clone_current_selected_song(&mut self) -> Option<Song> {
         if let Some(playlist_idx) = self.playlists_state.selected() {
             if let Some(song_idx) = self.songs_list_states[playlist_idx].selected() {
@@ -242,19 +786,352 @@ impl HomeTabState {
         }
         None
     }
+    fn clear_marks(&mut self) {
+        self.marked_songs.clear();
+        self.mark_anchor = None;
+    }
+    fn has_marks(&self) -> bool {
+        !self.marked_songs.is_empty() || self.mark_anchor.is_some()
+    }
+    // mark or unmark the song under the cursor, then go to the next one
+    fn toggle_mark_current_selected_song(&mut self) {
+        if let Some(song) = self.clone_current_selected_song() {
+            if !self.marked_songs.remove(&song.path) {
+                self.marked_songs.insert(song.path);
+            }
+            self.select_next_song();
+        }
+    }
+    // the first press starts a range at the cursor, the second marks the songs up to the cursor
+    fn toggle_mark_range(&mut self) {
+        let playlist_idx = match self.playlists_state.selected() {
+            Some(playlist_idx) => playlist_idx,
+            None => return,
+        };
+        match self.marked_range() {
+            Some(range) => {
+                let songs = &self.playlists[playlist_idx].songs;
+                self.marked_songs
+                    .extend(songs[range].iter().map(|s| s.path.clone()));
+                self.mark_anchor = None;
+            }
+            None => self.mark_anchor = self.songs_list_states[playlist_idx].selected(),
+        }
+    }
+    // the rows between the start of the range and the cursor
+    fn marked_range(&self) -> Option<RangeInclusive<usize>> {
+        let anchor = self.mark_anchor?;
+        let playlist_idx = self.playlists_state.selected()?;
+        let cursor = self.songs_list_states[playlist_idx].selected()?;
+        let last = self.playlists[playlist_idx].songs.len().checked_sub(1)?;
+        Some(anchor.min(cursor).min(last)..=anchor.max(cursor).min(last))
+    }
+    // all the songs of the playlist, or none when they all are marked
+    fn toggle_mark_all(&mut self) {
+        let playlist_idx = match self.playlists_state.selected() {
+            Some(playlist_idx) => playlist_idx,
+            None => return,
+        };
+        let songs = &self.playlists[playlist_idx].songs;
+        if songs.iter().all(|s| self.marked_songs.contains(&s.path)) {
+            self.clear_marks();
+        } else {
+            self.marked_songs
+                .extend(songs.iter().map(|s| s.path.clone()));
+            self.mark_anchor = None;
+        }
+    }
+    // what the bulk actions apply to: the marked songs, or the song under the cursor
+    fn target_song_indices(&self) -> Vec<usize> {
+        let playlist_idx = match self.playlists_state.selected() {
+            Some(playlist_idx) => playlist_idx,
+            None => return vec![],
+        };
+        let songs = &self.playlists[playlist_idx].songs;
+        let range = self.marked_range();
+        let marked: Vec<usize> = (0..songs.len())
+            .filter(|i| {
+                self.marked_songs.contains(&songs[*i].path)
+                    || range.as_ref().is_some_and(|r| r.contains(i))
+            })
+            .collect();
+        if !marked.is_empty() {
+            return marked;
+        }
+        self.songs_list_states[playlist_idx]
+            .selected()
+            .into_iter()
+            .collect()
+    }
+    fn clone_target_songs(&self) -> Vec<Song> {
+        match self.playlists_state.selected() {
+            Some(playlist_idx) => self
+                .target_song_indices()
+                .into_iter()
+                .map(|i| self.playlists[playlist_idx].songs[i].clone())
+                .collect(),
+            None => vec![],
+        }
+    }
+    // the playlist a typed name means: the one with that name, else the only one it starts
+    fn find_playlist_for_adding(&self, typed: &str) -> Result<Option<usize>, String> {
+        let name = typed.trim().to_lowercase();
+        let can_add = |p: &PlayList| p.smart.is_none() && p.name != DEFAULT_PLAYLIST_NAME;
+        if let Some(idx) = self
+            .playlists
+            .iter()
+            .position(|p| p.name.to_lowercase() == name)
+        {
+            if !can_add(&self.playlists[idx]) {
+                return Err(format!(
+                    "songs can not be added to {}",
+                    self.playlists[idx].name
+                ));
+            }
+            return Ok(Some(idx));
+        }
+        let starting: Vec<usize> = (0..self.playlists.len())
+            .filter(|i| {
+                let playlist = &self.playlists[*i];
+                can_add(playlist) && playlist.name.to_lowercase().starts_with(&name)
+            })
+            .collect();
+        match starting.as_slice() {
+            [] => Ok(None),
+            [idx] => Ok(Some(*idx)),
+            _ => Err(format!(
+                "{} playlists start with {}",
+                starting.len(),
+                typed.trim()
+            )),
+        }
+    }
+    // the songs it does not have yet, a name no playlist has makes a new playlist
+    fn add_target_songs_to_playlist(&mut self, name: &str) -> Result<(String, usize), String> {
+        let songs = self.clone_target_songs();
+        let playlist_idx = match self.find_playlist_for_adding(name)? {
+            Some(playlist_idx) => playlist_idx,
+            None => self.insert_playlist(name.trim().to_string())?,
+        };
+        let playlist = &mut self.playlists[playlist_idx];
+        let mut present: HashSet<PathBuf> = playlist.songs.iter().map(|s| s.path.clone()).collect();
+        let added: Vec<Song> = songs
+            .into_iter()
+            .filter(|s| present.insert(s.path.clone()))
+            .collect();
+        let added_count = added.len();
+        playlist.songs.extend(added);
+        playlist.sort_songs(&self.song_stats.lock().unwrap());
+        let name = playlist.name.clone();
+        self.save_playlists()
+            .map_err(|e| format!("Cannot save playlists: {}", e))?;
+        Ok((name, added_count))
+    }
+    // the default playlist is the library and the smart ones are built from stats,
+    // songs leave the others
+    fn remove_target_songs_from_playlist(&mut self) -> Result<usize, String> {
+        let playlist_idx = match self.playlists_state.selected() {
+            Some(playlist_idx) => playlist_idx,
+            None => return Ok(0),
+        };
+        let playlist = &self.playlists[playlist_idx];
+        if playlist.smart.is_some() {
+            return Err(format!("{} is filled from song stats", playlist.name));
+        }
+        if playlist.name == DEFAULT_PLAYLIST_NAME {
+            return Err(format!(
+                "{} is the library, songs leave it with their source",
+                playlist.name
+            ));
+        }
+        let targets: HashSet<usize> = self.target_song_indices().into_iter().collect();
+        let mut song_idx = 0;
+        self.playlists[playlist_idx].songs.retain(|_| {
+            song_idx += 1;
+            !targets.contains(&(song_idx - 1))
+        });
+        self.clear_marks();
+        self.clamp_song_selections();
+        self.save_playlists()
+            .map_err(|e| format!("Cannot save playlists: {}", e))?;
+        Ok(targets.len())
+    }
+    // the values all the target songs share, as the tag editor reads them
+    fn shared_tags(&self) -> String {
+        let songs = self.clone_target_songs();
+        let values: Vec<(TagField, String)> = TagField::ALL
+            .into_iter()
+            .filter_map(|field| {
+                let value = |s: &Song| match field {
+                    TagField::Title => s.title.clone(),
+                    TagField::Artist => s.artist.clone(),
+                    TagField::Album => s.album.clone(),
+                    TagField::Track => s.track.map(|t| t.to_string()),
+                };
+                let first = value(songs.first()?);
+                if songs.iter().any(|s| value(s) != first) {
+                    return None;
+                }
+                first.map(|v| (field, v))
+            })
+            .collect();
+        tags::format(&values)
+    }
+    // the mp3 files among the target songs, the tagged count and the files that failed
+    fn tag_target_songs(&mut self, changes: &[TagChange]) -> (usize, Vec<String>) {
+        let mut tagged = HashSet::new();
+        let mut problems = vec![];
+        for song in self.clone_target_songs() {
+            if !tags::is_editable(&song.path) || tagged.contains(&song.path) {
+                continue;
+            }
+            let fields: Vec<TagField> = changes.iter().map(|(field, _)| *field).collect();
+            let previous = tags::read(&song.path, &fields);
+            match tags::write(&song.path, changes) {
+                Ok(()) => {
+                    self.tag_undo.push((song.path.clone(), previous));
+                    tagged.insert(song.path);
+                }
+                Err(e) => problems.push(format!("{}: {}", song.name, e)),
+            }
+        }
+        if let Err(e) = self.reload_songs(&tagged) {
+            problems.push(format!("Cannot save playlists: {}", e));
+        }
+        (tagged.len(), problems)
+    }
+    // the playlists playlist.json keeps, for the undo history
+    fn saved_playlists(&self) -> Vec<(String, PlaylistContent)> {
+        self.playlists
+            .iter()
+            .filter(|p| p.smart.is_none())
+            .map(|p| {
+                let content = PlaylistContent {
+                    songs: p.songs.clone(),
+                    sort: p.sort,
+                };
+                (p.name.clone(), content)
+            })
+            .collect()
+    }
+    // the playlists as an edit found them, None removes the playlist
+    fn restore_playlists(
+        &mut self,
+        playlists: Vec<(String, Option<PlaylistContent>)>,
+        duplicate_replacements: Option<HashMap<String, String>>,
+    ) -> io::Result<()> {
+        for (name, content) in playlists {
+            let idx = self
+                .playlists
+                .iter()
+                .position(|p| p.smart.is_none() && p.name == name);
+            match (idx, content) {
+                (Some(idx), Some(content)) => {
+                    self.playlists[idx].songs = content.songs;
+                    self.playlists[idx].sort = content.sort;
+                }
+                (None, Some(content)) => {
+                    if let Ok(idx) = self.insert_playlist(name) {
+                        self.playlists[idx].songs = content.songs;
+                        self.playlists[idx].sort = content.sort;
+                    }
+                }
+                (Some(idx), None) => self.remove_playlist(idx),
+                (None, None) => {}
+            }
+        }
+        if let Some(duplicate_replacements) = duplicate_replacements {
+            self.duplicate_replacements = duplicate_replacements;
+        }
+        if self.duplicates_view.is_some() {
+            self.close_duplicates_view();
+        }
+        self.clear_marks();
+        self.clamp_song_selections();
+        self.refresh_smart_playlists();
+        self.save_playlists()
+    }
+    // the default playlist is never removed, so one is left to select
+    fn remove_playlist(&mut self, playlist_idx: usize) {
+        self.playlists.remove(playlist_idx);
+        self.songs_list_states.remove(playlist_idx);
+        match self.playlists_state.selected() {
+            Some(selected) if selected > playlist_idx => {
+                self.playlists_state.select(Some(selected - 1))
+            }
+            Some(selected) if selected == playlist_idx => {
+                self.focus = HomeTabStateFocus::Left;
+                self.playlists_state
+                    .select(Some(playlist_idx.min(self.playlists.len() - 1)));
+            }
+            _ => {}
+        }
+    }
+    // read the tags of the songs again, in every playlist that has them
+    fn reload_songs(&mut self, paths: &HashSet<PathBuf>) -> io::Result<()> {
+        let mut reloaded: HashMap<PathBuf, Song> = HashMap::new();
+        for playlist in self.playlists.iter_mut() {
+            for song in playlist.songs.iter_mut() {
+                if !paths.contains(&song.path) {
+                    continue;
+                }
+                let song_path = song.path.clone();
+                if let Some(new_song) = reloaded
+                    .get(&song_path)
+                    .cloned()
+                    .or_else(|| Song::new(song_path.clone()))
+                {
+                    reloaded.insert(song_path, new_song.clone());
+                    *song = new_song;
+                }
+            }
+        }
+        if reloaded.is_empty() {
+            return Ok(());
+        }
+        // sorted by a tag, the songs may have moved
+        let mut resorted = false;
+        for playlist_idx in 0..self.playlists.len() {
+            if self.playlists[playlist_idx].sort.is_some() {
+                self.resort_playlist(playlist_idx);
+                resorted = true;
+            }
+        }
+        if resorted {
+            self.save_playlists()?;
+        }
+        Ok(())
+    }
 }
 
+const DEFAULT_PLAYLIST_NAME: &str = "Default";
+
 struct PlayList {
     name: String,
     songs: Vec<Song>,
+    sort: Option<SongSort>,       // None keeps the scan order
+    smart: Option<SmartPlaylist>, // songs picked from song stats, not saved to playlist.json
+}
+impl PlayList {
+    fn sort_songs(&mut self, song_stats: &SongStatsDB) {
+        if let Some(sort) = self.sort {
+            self.songs.sort_by(|a, b| sort.compare(a, b, song_stats));
+        }
+    }
 }
 
 #[derive(Hash, PartialEq, Eq, Clone)]
 struct Song {
     name: String,
     path: PathBuf,
-    size: u16,
+    size: u64,
     format: String,
+    // from id3 tags if the file has them
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    track: Option<u32>,
+    duration: Option<Duration>,
 }
 impl Song {
     fn new(path: PathBuf) -> Option<Self> {
@@ -265,23 +1142,75 @@ impl Song {
                 .to_str()
                 .unwrap()
                 .to_string();
-            let size = path.metadata().unwrap().len() as u16;
+            let size = path.metadata().unwrap().len();
             let format = path
                 .extension()
                 .unwrap_or(&ffi::OsString::from("Unknown"))
                 .to_str()
                 .unwrap()
                 .to_string();
+            let tag = id3::Tag::read_from_path(&path).ok();
+            let title = tag.as_ref().and_then(|t| t.title()).map(String::from);
+            let artist = tag.as_ref().and_then(|t| t.artist()).map(String::from);
+            let album = tag.as_ref().and_then(|t| t.album()).map(String::from);
+            let track = tag.as_ref().and_then(|t| t.track());
+            // the tagged length, the playback fills it in from the decoder otherwise
+            let duration = tag
+                .as_ref()
+                .and_then(|t| t.duration())
+                .map(|ms| Duration::from_millis(ms as u64));
             Some(Self {
                 name,
                 path,
                 size,
                 format,
+                title,
+                artist,
+                album,
+                track,
+                duration,
             })
         } else {
             None
         }
     }
+    fn display_title(&self) -> String {
+        self.title.clone().unwrap_or_else(|| self.name.clone())
+    }
+}
+#[cfg(test)]
+impl Song {
+    // a song without tags that is not read from disk, tests set the fields they need
+    fn untagged(path: &str) -> Self {
+        let path = PathBuf::from(path);
+        Self {
+            name: path.file_name().unwrap().to_string_lossy().to_string(),
+            format: path.extension().unwrap().to_string_lossy().to_string(),
+            path,
+            size: 0,
+            title: None,
+            artist: None,
+            album: None,
+            track: None,
+            duration: None,
+        }
+    }
+}
+// an empty folder of the tests under the temp folder, `name` keeps the tests apart
+#[cfg(test)]
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("songbreeze-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// what the edit popup of the sources tab was opened for
+#[derive(Clone, Copy)]
+enum SourceEdit {
+    Add,
+    Label,
+    Excludes,
 }
 
 // source tab state
@@ -289,6 +1218,15 @@ struct SourceTabState {
     source_db: SourceDB,
     sources_list_state: ListState,
     configuration: Rc<Configuration>,
+    // the last typed path and what was found there, counting files is too slow for every frame
+    path_status: Option<(String, (String, bool))>,
+    browser: Option<FolderBrowser>, // shown over the sources list
+    editing: SourceEdit,
+    removal: Option<SourceRemoval>, // waiting to be confirmed
+    sources_revision: u64,          // bumped on every save, the undo history looks for edits then
+    // of the settings, what the browser lists and counts
+    scan_recursive: bool,
+    scan_extensions: Vec<String>,
 }
 impl SourceTabState {
     fn new(configuration: Rc<Configuration>) -> Self {
@@ -296,6 +1234,13 @@ impl SourceTabState {
             source_db: SourceDB::new_empty(),
             sources_list_state: ListState::default(),
             configuration,
+            path_status: None,
+            browser: None,
+            editing: SourceEdit::Add,
+            removal: None,
+            sources_revision: 0,
+            scan_recursive: false,
+            scan_extensions: vec![],
         }
     }
 }
@@ -306,8 +1251,58 @@ impl SourceTabState {
         self.source_db = source;
         self.sources_list_state.select(Some(0));
     }
-    fn cloned_sources(&self) -> Vec<String> {
-        self.source_db.sources.clone()
+    fn save_sources(&mut self) -> io::Result<()> {
+        self.sources_revision += 1;
+        fs::write(
+            &self.configuration.source_file_path,
+            serde_json::to_string_pretty(&self.source_db).unwrap(),
+        )
+    }
+    fn current_selected_source(&mut self) -> Option<&mut Source> {
+        let current_idx = self.sources_list_state.selected()?;
+        self.source_db.sources.get_mut(current_idx)
+    }
+    // what a scan found in each source, in the order of the sources
+    fn record_scan(&mut self, scan_results: Vec<Option<(usize, u64)>>) -> io::Result<()> {
+        let now = stats::now();
+        for (source, scan_result) in self.source_db.sources.iter_mut().zip(scan_results) {
+            if let Some((tracks, size)) = scan_result {
+                source.tracks = tracks;
+                source.size = size;
+                source.last_scan = Some(now);
+            }
+        }
+        self.save_sources()
+    }
+    // the source and whether it is enabled now, not saved
+    fn toggle_current_selected_source(&mut self) -> Option<(String, bool)> {
+        let source = self.current_selected_source()?;
+        source.enabled = !source.enabled;
+        Some((source.display_name().to_string(), source.enabled))
+    }
+    // the source and whether it is scanned recursively now, not saved
+    fn toggle_current_selected_source_recursive(&mut self) -> Option<(String, bool)> {
+        let default_recursive = self.scan_recursive;
+        let source = self.current_selected_source()?;
+        let recursive = !source.is_recursive(default_recursive);
+        source.recursive = Some(recursive);
+        Some((source.display_name().to_string(), recursive))
+    }
+    // the edit popup for the label or the excludes of the selected source, with the current value
+    fn edit_current_selected_source(&mut self, editing: SourceEdit, app_state: &mut GlobalState) {
+        let (value, prompt) = match (self.current_selected_source(), editing) {
+            (Some(source), SourceEdit::Label) => {
+                (source.label.clone().unwrap_or_default(), "Label:")
+            }
+            (Some(source), SourceEdit::Excludes) => (
+                source.excludes.join(", "),
+                "Exclude patterns, comma separated:",
+            ),
+            _ => return,
+        };
+        self.editing = editing;
+        app_state.switch_mode_to_edit(prompt);
+        app_state.input.set(&value);
     }
     fn select_next(&mut self) {
         if self.source_db.sources.is_empty() {
@@ -332,141 +1327,723 @@ impl SourceTabState {
                 .select(Some(self.source_db.sources.len() - 1));
         }
     }
-    fn add_source(&mut self, source: String) {
-        if source.is_empty() {
-            return;
+    // the typed path is expanded and stored absolute, the stored path
+    fn add_source(&mut self, input: &str) -> Result<String, String> {
+        let path = paths::expand(input);
+        let source = path.to_string_lossy().to_string();
+        if !path.exists() {
+            return Err(format!("{} does not exist", source));
         }
-        self.source_db.sources.push(source);
-        fs::write(
-            &self.configuration.source_file_path,
-            serde_json::to_string_pretty(&self.source_db).unwrap(),
-        )
-        .unwrap();
+        if !path.is_dir() {
+            return Err(format!("{} is not a folder", source));
+        }
+        if self.is_source(&path) {
+            return Err(format!("{} is already a source", source));
+        }
+        self.source_db.sources.push(Source::new(source.clone()));
+        self.save_sources()
+            .map_err(|e| format!("Cannot save sources: {}", e))?;
+        Ok(source)
     }
-    fn delete_current_selected_source(&mut self) {
-        let current_idx = self.sources_list_state.selected().unwrap();
-        if current_idx >= self.source_db.sources.len() {
-            return;
+    // from the selected source, or the home folder
+    fn open_browser(&mut self) {
+        let start = self
+            .sources_list_state
+            .selected()
+            .and_then(|i| self.source_db.sources.get(i))
+            .map(|source| paths::expand(&source.path))
+            .filter(|path| path.is_dir())
+            .unwrap_or_else(|| paths::expand("~"));
+        self.browser = Some(FolderBrowser::new(
+            start,
+            self.scan_recursive,
+            self.scan_extensions.clone(),
+        ));
+    }
+    // sources added by hand may not be normalized
+    fn is_source(&self, path: &Path) -> bool {
+        self.source_db
+            .sources
+            .iter()
+            .any(|source| paths::expand(&source.path) == path)
+    }
+    fn check_path(&self, input: &str, settings: &Settings) -> (String, bool) {
+        let path = paths::expand(input);
+        let shown = path.to_string_lossy();
+        if !path.exists() {
+            return (format!("{}: no such folder", shown), false);
         }
-        self.source_db.sources.remove(current_idx);
-        fs::write(
-            &self.configuration.source_file_path,
-            serde_json::to_string_pretty(&self.source_db).unwrap(),
-        )
-        .unwrap();
+        if !path.is_dir() {
+            return (format!("{}: not a folder", shown), false);
+        }
+        if self.is_source(&path) {
+            return (format!("{}: already a source", shown), false);
+        }
+        let (count, complete) = paths::count_audio_files(
+            &path,
+            settings.scan_recursive,
+            &settings.scan_extensions,
+            paths::COUNT_ENTRIES_LIMIT,
+        );
+        let more = if complete { "" } else { "+" };
+        (format!("{}: {}{} audio files", shown, count, more), true)
+    }
+    // the sources as an edit found them
+    fn restore_sources(&mut self, sources: Vec<Source>) -> io::Result<()> {
+        self.source_db.sources = sources;
+        self.removal = None;
+        self.path_status = None;
+        let len = self.source_db.sources.len();
+        let selected = match self.sources_list_state.selected() {
+            _ if len == 0 => None,
+            Some(selected) => Some(selected.min(len - 1)),
+            None => Some(0),
+        };
+        self.sources_list_state.select(selected);
+        self.save_sources()
+    }
+    // the playlists are asked what it takes away before the removal is confirmed
+    fn request_removal_of_current_selected_source(&mut self) {
+        let current_idx = match self.sources_list_state.selected() {
+            Some(current_idx) if current_idx < self.source_db.sources.len() => current_idx,
+            _ => return,
+        };
+        self.removal = Some(SourceRemoval::new(
+            &self.source_db.sources,
+            current_idx,
+            self.scan_recursive,
+        ));
+    }
+    // the removed source, not saved
+    fn delete_source(&mut self, source_idx: usize) -> Source {
+        let source = self.source_db.sources.remove(source_idx);
         if !self.source_db.sources.is_empty() {
             self.sources_list_state
                 .select(Some(self.source_db.sources.len() - 1));
         } else {
             self.sources_list_state.select(None);
         }
+        source
     }
 }
 
-// source json
-#[derive(Serialize, Deserialize)]
-struct SourceDB {
-    sources: Vec<String>,
+// stats tab state
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StatsRange {
+    Week,
+    Month,
+    AllTime,
 }
-impl SourceDB {
-    fn new_empty() -> Self {
-        Self { sources: vec![] }
+impl StatsRange {
+    fn title(&self) -> &'static str {
+        match self {
+            StatsRange::Week => "Last 7 days",
+            StatsRange::Month => "Last 30 days",
+            StatsRange::AllTime => "All time",
+        }
+    }
+    fn since(&self) -> Option<u64> {
+        const DAY_SECS: u64 = 24 * 60 * 60;
+        match self {
+            StatsRange::Week => Some(stats::now().saturating_sub(7 * DAY_SECS)),
+            StatsRange::Month => Some(stats::now().saturating_sub(30 * DAY_SECS)),
+            StatsRange::AllTime => None,
+        }
+    }
+}
+struct StatsTabState {
+    listening_history: Arc<Mutex<ListeningHistory>>,
+    range: StatsRange,
+    report: Option<ListeningReport>,
+    report_revision: u64, // history revision the report was computed from
+}
+impl StatsTabState {
+    fn new(listening_history: Arc<Mutex<ListeningHistory>>) -> Self {
+        Self {
+            listening_history,
+            range: StatsRange::Week,
+            report: None,
+            report_revision: 0,
+        }
+    }
+}
+impl StatsTabState {
+    // recompute the report when it is missing or the history changed, the tags of the songs
+    // come from the library
+    fn refresh(&mut self, home_tab_state: &HomeTabState) {
+        let listening_history = self.listening_history.lock().unwrap();
+        if self.report.is_some() && self.report_revision == listening_history.revision {
+            return;
+        }
+        self.report = Some(ListeningReport::compute(
+            &listening_history.entries,
+            self.range.since(),
+            &home_tab_state.songs_by_path(),
+        ));
+        self.report_revision = listening_history.revision;
+    }
+    fn cycle_range(&mut self) {
+        self.range = match self.range {
+            StatsRange::Week => StatsRange::Month,
+            StatsRange::Month => StatsRange::AllTime,
+            StatsRange::AllTime => StatsRange::Week,
+        };
+        // computed again before the next draw
+        self.report = None;
     }
 }
 
 // settings tab state
-struct SettingsState {}
+struct SettingsState {
+    settings: Settings,
+    settings_list_state: ListState,
+    changed_item: Option<SettingItem>, // applied to the other states by the main loop
+    themes: Vec<(String, Theme)>,      // built-in and user themes by name
+    configuration: Rc<Configuration>,
+}
+impl SettingsState {
+    fn new(configuration: Rc<Configuration>) -> Self {
+        Self {
+            settings: Settings::default(),
+            settings_list_state: ListState::default(),
+            changed_item: None,
+            themes: vec![],
+            configuration,
+        }
+    }
+}
+impl SettingsState {
+    // also returns the problems found in the theme files
+    fn load_settings(&mut self) -> Vec<String> {
+        self.settings = Settings::load(&self.configuration.settting_file_path);
+        self.settings_list_state.select(Some(0));
+        let (themes, problems) = theme::load_themes(&self.configuration.themes_folder_path);
+        self.themes = themes;
+        problems
+    }
+    // NO_COLOR wins over the theme setting
+    fn theme(&self) -> Theme {
+        if theme::no_color() {
+            return Theme::monochrome();
+        }
+        self.themes
+            .iter()
+            .find(|(name, _)| *name == self.settings.theme)
+            .map_or_else(Theme::dark, |(_, theme)| *theme)
+    }
+    fn save_settings(&self) -> io::Result<()> {
+        self.settings.save(&self.configuration.settting_file_path)
+    }
+    fn select_next(&mut self) {
+        let i = self.settings_list_state.selected().unwrap_or(0);
+        self.settings_list_state
+            .select(Some((i + 1) % SettingItem::ALL.len()));
+    }
+    fn select_previous(&mut self) {
+        let i = self.settings_list_state.selected().unwrap_or(0);
+        self.settings_list_state.select(Some(
+            (i + SettingItem::ALL.len() - 1) % SettingItem::ALL.len(),
+        ));
+    }
+    fn current_selected_item(&self) -> SettingItem {
+        SettingItem::ALL[self.settings_list_state.selected().unwrap_or(0)]
+    }
+}
 
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 enum PlayMode {
     SingleLoop,
     ListLoop,
     ListRandom,
     AllListRandom,
 }
+impl PlayMode {
+    fn title(&self) -> &'static str {
+        match self {
+            PlayMode::SingleLoop => "Single loop",
+            PlayMode::ListLoop => "List loop",
+            PlayMode::ListRandom => "List random",
+            PlayMode::AllListRandom => "All lists random",
+        }
+    }
+    fn next(&self) -> Self {
+        match self {
+            PlayMode::SingleLoop => PlayMode::ListLoop,
+            PlayMode::ListLoop => PlayMode::ListRandom,
+            PlayMode::ListRandom => PlayMode::AllListRandom,
+            PlayMode::AllListRandom => PlayMode::SingleLoop,
+        }
+    }
+    // the song to play after `song` once the queue is empty, skipping a looped song moves on
+    fn follow_up(
+        &self,
+        song: &Song,
+        skipped: bool,
+        playing_list: &[Song],
+        library: &[Song],
+    ) -> Option<Song> {
+        // RandomState is seeded randomly, good enough to shuffle
+        let random_song = |songs: &[Song]| {
+            if songs.is_empty() {
+                return None;
+            }
+            let i = RandomState::new().build_hasher().finish() as usize % songs.len();
+            Some(songs[i].clone())
+        };
+        match self {
+            PlayMode::SingleLoop if !skipped => Some(song.clone()),
+            PlayMode::SingleLoop | PlayMode::ListLoop => {
+                let i = playing_list.iter().position(|s| s.path == song.path)?;
+                Some(playing_list[(i + 1) % playing_list.len()].clone())
+            }
+            PlayMode::ListRandom => random_song(playing_list),
+            PlayMode::AllListRandom => random_song(library),
+        }
+    }
+}
 // playback
 // 1. play mode: single loop, list loop, list random, all lists random
 // 2. 'p' to resume/play current or pause
 // 3. 'q' to push current song to the end of the list
 // 4. 's' to stop and clear the queue
 // 5. 'm' to change mode
-// 6. 'N'/'n': previous/next song
+// 6. '>' next song
 //
 // new a thread to play song, current thread send song to the play thread and after played a song
-// send a single to the current thread to send next song
+// send a single to the current thread to send next song.
+// the play thread records finished and skipped songs in the song stats and the listening history
+enum PlaybackOrder {
+    Play,
+    Pause,
+    Next,
+    Stop,
+    SetVolume(u8), // 0 to 100
+    Seek(Duration),
+}
+// what the playback thread reports to the ui
+enum PlaybackEvent {
+    Broken(PathBuf, String), // a song that can not be opened or decoded, it was skipped
+    NoOutput(String),        // no audio device, nothing can be played
+    NotSaved(String),        // the play counts or the history could not be written
+}
+
+fn open_song(path: &Path) -> Result<Decoder<BufReader<File>>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    Decoder::new(BufReader::new(file)).map_err(|e| e.to_string())
+}
+
+// the song in the sink and how far it is played, shared with the ui
+#[derive(Clone)]
+struct NowPlaying {
+    song: Song,
+    played: Duration,            // position when the clock last stopped
+    resumed_at: Option<Instant>, // None while paused
+}
+impl NowPlaying {
+    fn new(song: Song) -> Self {
+        Self {
+            song,
+            played: Duration::ZERO,
+            resumed_at: Some(Instant::now()),
+        }
+    }
+}
+impl NowPlaying {
+    fn pause(&mut self) {
+        if let Some(resumed_at) = self.resumed_at.take() {
+            self.played += resumed_at.elapsed();
+        }
+    }
+    fn resume(&mut self) {
+        if self.resumed_at.is_none() {
+            self.resumed_at = Some(Instant::now());
+        }
+    }
+    fn seek(&mut self, position: Duration) {
+        self.played = position;
+        if self.resumed_at.is_some() {
+            self.resumed_at = Some(Instant::now());
+        }
+    }
+    fn is_paused(&self) -> bool {
+        self.resumed_at.is_none()
+    }
+    fn position(&self) -> Duration {
+        let position = self.played + self.resumed_at.map_or(Duration::ZERO, |r| r.elapsed());
+        self.song.duration.map_or(position, |d| position.min(d))
+    }
+    // ratio for the progress gauge and "1:23 / 3:45"
+    fn progress(&self) -> (f64, String) {
+        let position = self.position();
+        match self.song.duration.filter(|d| !d.is_zero()) {
+            Some(duration) => (
+                position.as_secs_f64() / duration.as_secs_f64(),
+                format!(
+                    "{} / {}",
+                    columns::format_duration(position),
+                    columns::format_duration(duration)
+                ),
+            ),
+            None => (0.0, columns::format_duration(position)),
+        }
+    }
+}
+
 struct Playback {
-    tx: Sender<usize>,
-    // play_mode: PlayMode,
+    tx: Sender<PlaybackOrder>,
+    events: Receiver<PlaybackEvent>,
+    play_mode: Arc<Mutex<PlayMode>>,
+    volume: u8,
     songs_queue: Arc<Mutex<VecDeque<Song>>>,
+    // where the play mode picks the next song from when the queue is empty
+    playing_list: Arc<Mutex<Vec<Song>>>,
+    library: Arc<Mutex<Vec<Song>>>,
+    now_playing: Arc<Mutex<Option<NowPlaying>>>,
 }
 
-//todo: track duration
 impl Playback {
-    fn new() -> Self {
+    fn new(
+        song_stats: Arc<Mutex<SongStatsDB>>,
+        listening_history: Arc<Mutex<ListeningHistory>>,
+    ) -> Self {
         let (tx, rx) = mpsc::channel();
+        let (events_tx, events) = mpsc::channel();
+        let play_mode = Arc::new(Mutex::new(PlayMode::ListLoop));
         let songs_queue: Arc<Mutex<VecDeque<Song>>> = Arc::new(Mutex::new(VecDeque::new()));
+        let playing_list: Arc<Mutex<Vec<Song>>> = Arc::new(Mutex::new(vec![]));
+        let library: Arc<Mutex<Vec<Song>>> = Arc::new(Mutex::new(vec![]));
+        let now_playing: Arc<Mutex<Option<NowPlaying>>> = Arc::new(Mutex::new(None));
+        let play_mode_clone = play_mode.clone();
         let songs_queue_clone = songs_queue.clone();
+        let playing_list_clone = playing_list.clone();
+        let library_clone = library.clone();
+        let now_playing_clone = now_playing.clone();
         thread::spawn(move || {
+            let play_mode = play_mode_clone;
             let songs_queue = songs_queue_clone;
-            let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-            let sink = Sink::try_new(&stream_handle).unwrap();
-            for order in rx {
-                match order {
-                    0 => {
-                        // play
-                        let mut songs_queue = songs_queue.lock().unwrap();
-                        if let Some(song) = songs_queue.pop_front() {
-                            let file = File::open(song.path).unwrap();
-                            let source = Decoder::new(BufReader::new(file)).unwrap();
-                            sink.append(source);
+            let playing_list = playing_list_clone;
+            let library = library_clone;
+            let now_playing = now_playing_clone;
+            let (_stream, stream_handle) = match OutputStream::try_default() {
+                Ok(output) => output,
+                Err(error) => {
+                    events_tx
+                        .send(PlaybackEvent::NoOutput(error.to_string()))
+                        .ok();
+                    return;
+                }
+            };
+            let mut volume = 1.0;
+            let mut sink = Sink::try_new(&stream_handle).unwrap();
+            // the song in the sink
+            let mut current_song: Option<(Song, ListenClock)> = None;
+            // songs that failed to open or decode, not tried again
+            let mut broken: HashSet<PathBuf> = HashSet::new();
+            let report_broken = |song: &Song, error: String, broken: &mut HashSet<PathBuf>| {
+                broken.insert(song.path.clone());
+                events_tx
+                    .send(PlaybackEvent::Broken(song.path.clone(), error))
+                    .ok();
+            };
+            // the queue first, then the play mode decides what follows the last song,
+            // broken songs are skipped the way the user would skip them
+            let play_next =
+                |sink: &Sink, last_song: Option<(&Song, bool)>, broken: &mut HashSet<PathBuf>| {
+                    let mut last_song = last_song.map(|(song, skipped)| (song.clone(), skipped));
+                    // a list of broken songs must not keep the thread busy forever
+                    let mut attempts =
+                        songs_queue.lock().unwrap().len() + playing_list.lock().unwrap().len() + 1;
+                    while attempts > 0 {
+                        attempts -= 1;
+                        let song = songs_queue.lock().unwrap().pop_front().or_else(|| {
+                            let (last_song, skipped) = last_song.as_ref()?;
+                            play_mode.lock().unwrap().follow_up(
+                                last_song,
+                                *skipped,
+                                &playing_list.lock().unwrap(),
+                                &library.lock().unwrap(),
+                            )
+                        });
+                        let song = match song {
+                            Some(song) => song,
+                            None => break,
+                        };
+                        if broken.contains(&song.path) {
+                            last_song = Some((song, true));
+                            continue;
+                        }
+                        match open_song(&song.path) {
+                            Ok(source) => {
+                                let mut song = song;
+                                if song.duration.is_none() {
+                                    song.duration = source.total_duration();
+                                }
+                                sink.append(source);
+                                *now_playing.lock().unwrap() = Some(NowPlaying::new(song.clone()));
+                                return Some((song, ListenClock::start()));
+                            }
+                            Err(error) => {
+                                report_broken(&song, error, broken);
+                                last_song = Some((song, true));
+                            }
+                        }
+                    }
+                    *now_playing.lock().unwrap() = None;
+                    None
+                };
+            let record = |song: &Song, listen_clock: ListenClock, end: PlaybackEnd| {
+                let counted = match end {
+                    PlaybackEnd::Finished => song_stats.lock().unwrap().record_finished(&song.path),
+                    PlaybackEnd::Skipped => song_stats.lock().unwrap().record_skipped(&song.path),
+                    PlaybackEnd::Stopped => Ok(()),
+                };
+                if let Err(error) = counted {
+                    events_tx
+                        .send(PlaybackEvent::NotSaved(format!(
+                            "Cannot save stats: {}",
+                            error
+                        )))
+                        .ok();
+                }
+                let appended = listening_history.lock().unwrap().append(HistoryEntry {
+                    song_path: song.path.to_string_lossy().to_string(),
+                    started_at: listen_clock.started_at,
+                    listened_secs: listen_clock.listened().as_secs(),
+                    completed: end == PlaybackEnd::Finished,
+                    stopped: end == PlaybackEnd::Stopped,
+                });
+                if let Err(error) = appended {
+                    events_tx
+                        .send(PlaybackEvent::NotSaved(format!(
+                            "Cannot save listening history: {}",
+                            error
+                        )))
+                        .ok();
+                }
+            };
+            loop {
+                match rx.recv_timeout(Duration::from_millis(200)) {
+                    Ok(PlaybackOrder::Play) => {
+                        // play: resume or start the next song in the queue
+                        sink.play();
+                        if let Some((_, listen_clock)) = current_song.as_mut() {
+                            listen_clock.resume();
+                        }
+                        if let Some(now_playing) = now_playing.lock().unwrap().as_mut() {
+                            now_playing.resume();
+                        }
+                        if sink.empty() {
+                            current_song = play_next(&sink, None, &mut broken);
                         }
                     }
-                    1 => {
-                        // pause
+                    Ok(PlaybackOrder::Pause) => {
                         sink.pause();
+                        if let Some((_, listen_clock)) = current_song.as_mut() {
+                            listen_clock.pause();
+                        }
+                        if let Some(now_playing) = now_playing.lock().unwrap().as_mut() {
+                            now_playing.pause();
+                        }
+                    }
+                    Ok(order @ (PlaybackOrder::Next | PlaybackOrder::Stop)) => {
+                        // skip or stop the current song, a stopped sink can not be reused
+                        let skipped_song = current_song.take().map(|(song, listen_clock)| {
+                            let end = match order {
+                                PlaybackOrder::Next => PlaybackEnd::Skipped,
+                                _ => PlaybackEnd::Stopped,
+                            };
+                            record(&song, listen_clock, end);
+                            song
+                        });
+                        sink.stop();
+                        sink = Sink::try_new(&stream_handle).unwrap();
+                        sink.set_volume(volume);
+                        if let PlaybackOrder::Next = order {
+                            current_song = play_next(
+                                &sink,
+                                skipped_song.as_ref().map(|s| (s, true)),
+                                &mut broken,
+                            );
+                        } else {
+                            songs_queue.lock().unwrap().clear();
+                            *now_playing.lock().unwrap() = None;
+                        }
+                    }
+                    Ok(PlaybackOrder::SetVolume(percent)) => {
+                        volume = percent as f32 / 100.0;
+                        sink.set_volume(volume);
+                    }
+                    Ok(PlaybackOrder::Seek(position)) => {
+                        // rodio can not seek a playing source, decode the song again from there
+                        if let Some((song, listen_clock)) = current_song.take() {
+                            let paused = sink.is_paused();
+                            sink.stop();
+                            sink = Sink::try_new(&stream_handle).unwrap();
+                            sink.set_volume(volume);
+                            if paused {
+                                sink.pause();
+                            }
+                            match open_song(&song.path) {
+                                Ok(source) => {
+                                    sink.append(source.skip_duration(position));
+                                    if let Some(now_playing) = now_playing.lock().unwrap().as_mut()
+                                    {
+                                        now_playing.seek(position);
+                                    }
+                                    current_song = Some((song, listen_clock));
+                                }
+                                // the file changed since it started playing
+                                Err(error) => {
+                                    record(&song, listen_clock, PlaybackEnd::Stopped);
+                                    report_broken(&song, error, &mut broken);
+                                    current_song =
+                                        play_next(&sink, Some((&song, true)), &mut broken);
+                                }
+                            }
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                // a finished song leaves the sink empty
+                if sink.empty() {
+                    if let Some((song, listen_clock)) = current_song.take() {
+                        record(&song, listen_clock, PlaybackEnd::Finished);
+                        current_song = play_next(&sink, Some((&song, false)), &mut broken);
                     }
-                    _ => {}
                 }
             }
         });
-        Self { tx, songs_queue }
+        Self {
+            tx,
+            events,
+            play_mode,
+            volume: 100,
+            songs_queue,
+            playing_list,
+            library,
+            now_playing,
+        }
     }
 }
 
 impl Playback {
+    // without an audio output the thread is gone, and the orders go nowhere
+    fn send(&self, order: PlaybackOrder) {
+        self.tx.send(order).ok();
+    }
     fn play(&mut self) {
-        self.tx.send(0).unwrap();
+        self.send(PlaybackOrder::Play);
     }
     fn pause(&mut self) {
-        self.tx.send(1).unwrap();
+        self.send(PlaybackOrder::Pause);
+    }
+    fn next(&mut self) {
+        self.send(PlaybackOrder::Next);
+    }
+    fn stop(&mut self) {
+        self.send(PlaybackOrder::Stop);
+    }
+    fn toggle_pause(&mut self) {
+        match self.now_playing() {
+            Some(now_playing) if !now_playing.is_paused() => self.pause(),
+            _ => self.play(),
+        }
+    }
+    fn now_playing(&self) -> Option<NowPlaying> {
+        self.now_playing.lock().unwrap().clone()
+    }
+    fn set_volume(&mut self, volume: u8) {
+        self.volume = volume.min(100);
+        self.send(PlaybackOrder::SetVolume(self.volume));
+    }
+    fn seek(&mut self, position: Duration) {
+        self.send(PlaybackOrder::Seek(position));
+    }
+    fn play_mode(&self) -> PlayMode {
+        *self.play_mode.lock().unwrap()
+    }
+    fn set_play_mode(&mut self, play_mode: PlayMode) {
+        *self.play_mode.lock().unwrap() = play_mode;
+    }
+    fn cycle_play_mode(&mut self) {
+        let play_mode = self.play_mode().next();
+        self.set_play_mode(play_mode);
+    }
+    // the playlist the user plays from and all songs, for the play mode
+    fn set_playing_list(&mut self, playing_list: Vec<Song>, library: Vec<Song>) {
+        *self.playing_list.lock().unwrap() = playing_list;
+        *self.library.lock().unwrap() = library;
     }
-    fn stop(&mut self) {}
     fn inqueue(&mut self, song: Song) {
         self.songs_queue.lock().unwrap().push_back(song);
     }
+    // skip whatever is playing and play the song right away
+    fn play_now(&mut self, song: Song) {
+        self.songs_queue.lock().unwrap().push_front(song);
+        self.next();
+    }
 }
 
 enum InputMode {
     Normal,
     Edit,
+    Search,
+    Command, // the `:` command line
+    ConfirmQuit,
+    ConfirmSourceRemoval, // prune or keep the playlist entries of a removed source
+    Help,                 // the overlay listing the keys
+    MessageLog,
 }
+// the tabs by their index in the tab titles
+const HOME_TAB: usize = 0;
+const SOURCES_TAB: usize = 1;
+const STATS_TAB: usize = 2;
+const SETTINGS_TAB: usize = 3;
 // app global state
 struct GlobalState {
     tab_titles: Vec<String>,
     selected_tab_idx: Option<usize>,
     input_mode: InputMode,
-    input_stream: Vec<String>,
-    configuration: Rc<Configuration>,
+    input: TextInput,
+    edit_prompt: &'static str, // title of the edit popup
+    edit_histories: HashMap<&'static str, InputHistory>, // by prompt
     playback: Playback,
+    search: Option<Search>, // current or last search, kept for 'n'/'N'
+    should_quit: bool,
+    mini_player: bool,   // only the player is drawn
+    overlay_scroll: u16, // of the help or message log overlay
+    theme: Theme,
+    notifications: Notifications, // the status line and the message log
+    command_history: InputHistory,
+    // of the command line or the edit popup: line before the completed word, candidates,
+    // the one shown
+    completion: Option<(String, Vec<String>, usize)>,
+    undo_history: UndoHistory,
 }
 impl GlobalState {
-    fn new(configuration: Rc<Configuration>) -> GlobalState {
+    fn new(
+        song_stats: Arc<Mutex<SongStatsDB>>,
+        listening_history: Arc<Mutex<ListeningHistory>>,
+    ) -> GlobalState {
         GlobalState {
             tab_titles: vec![],
             selected_tab_idx: None,
             input_mode: InputMode::Normal,
-            input_stream: vec![],
-            configuration,
-            playback: Playback::new(),
+            input: TextInput::new(),
+            edit_prompt: "",
+            edit_histories: HashMap::new(),
+            playback: Playback::new(song_stats, listening_history),
+            search: None,
+            should_quit: false,
+            mini_player: false,
+            overlay_scroll: 0,
+            theme: Theme::dark(),
+            notifications: Notifications::new(),
+            command_history: InputHistory::new(),
+            completion: None,
+            undo_history: UndoHistory::new(),
         }
     }
 }
@@ -510,9 +2087,71 @@ impl GlobalState {
     fn switch_mode_to_normal(&mut self) {
         self.input_mode = InputMode::Normal;
     }
-    fn switch_mode_to_edit(&mut self) {
+    fn switch_mode_to_edit(&mut self, edit_prompt: &'static str) {
+        self.edit_prompt = edit_prompt;
+        self.edit_history().reset();
+        self.completion = None;
         self.input_mode = InputMode::Edit;
     }
+    fn switch_mode_to_search(&mut self) {
+        self.input_mode = InputMode::Search;
+    }
+    fn switch_mode_to_help(&mut self) {
+        self.overlay_scroll = 0;
+        self.input_mode = InputMode::Help;
+    }
+    fn switch_mode_to_message_log(&mut self) {
+        self.overlay_scroll = 0;
+        self.input_mode = InputMode::MessageLog;
+    }
+    fn switch_mode_to_command(&mut self) {
+        self.input.clear();
+        self.command_history.reset();
+        self.completion = None;
+        self.input_mode = InputMode::Command;
+    }
+    // what was entered before in the edit popup with the current prompt
+    fn edit_history(&mut self) -> &mut InputHistory {
+        self.edit_histories
+            .entry(self.edit_prompt)
+            .or_insert_with(InputHistory::new)
+    }
+    // up and down in the edit popup or the command line
+    fn browse_input_history(&mut self, older: bool) {
+        let history = match self.input_mode {
+            InputMode::Command => &mut self.command_history,
+            _ => self
+                .edit_histories
+                .entry(self.edit_prompt)
+                .or_insert_with(InputHistory::new),
+        };
+        history.browse(&mut self.input, older);
+    }
+    // tab in the command line or the edit popup, the next tab shows the next candidate,
+    // a single candidate is taken and the next tab completes from there
+    fn complete_input(&mut self, completions: Completions) {
+        let line = self.input.text();
+        let completion = match self.completion.take() {
+            Some((base, candidates, current))
+                if line == format!("{}{}", base, candidates[current]) =>
+            {
+                let next = (current + 1) % candidates.len();
+                Some((base, candidates, next))
+            }
+            _ => {
+                let (word_start, candidates) = completions(&line);
+                if candidates.is_empty() {
+                    None
+                } else {
+                    Some((line[..word_start].to_string(), candidates, 0))
+                }
+            }
+        };
+        if let Some((base, candidates, current)) = completion.as_ref() {
+            self.input.set(&format!("{}{}", base, candidates[*current]));
+        }
+        self.completion = completion.filter(|(_, candidates, _)| candidates.len() > 1);
+    }
 }
 
 //todo: add and delete source
@@ -521,6 +2160,11 @@ struct Configuration {
     settting_file_path: PathBuf,
     source_file_path: PathBuf,
     playlist_file_path: PathBuf,
+    stats_file_path: PathBuf,
+    history_file_path: PathBuf,
+    keymap_file_path: PathBuf,
+    themes_folder_path: PathBuf,
+    crash_log_file_path: PathBuf,
 }
 impl Configuration {
     fn new() -> Self {
@@ -529,11 +2173,23 @@ impl Configuration {
             settting_file_path: PathBuf::new(),
             source_file_path: PathBuf::new(),
             playlist_file_path: PathBuf::new(),
+            stats_file_path: PathBuf::new(),
+            history_file_path: PathBuf::new(),
+            keymap_file_path: PathBuf::new(),
+            themes_folder_path: PathBuf::new(),
+            crash_log_file_path: PathBuf::new(),
         };
         configure.folder_path = PathBuf::from(env::var("HOME").unwrap()).join(".songbreeze");
         configure.settting_file_path = configure.folder_path.join("setting.json");
         configure.source_file_path = configure.folder_path.join("source.json");
         configure.playlist_file_path = configure.folder_path.join("playlist.json");
+        // written by the app on first play or rating, no need to ask for them
+        configure.stats_file_path = configure.folder_path.join("stats.json");
+        configure.history_file_path = configure.folder_path.join("history.jsonl");
+        // optional, the default keys are used without it
+        configure.keymap_file_path = configure.folder_path.join("keymap.json");
+        configure.themes_folder_path = configure.folder_path.join("themes");
+        configure.crash_log_file_path = configure.folder_path.join("crash.log");
 
         // helper function for creating folder or file while asking user
         let create_ff_while_asking = |path: &Path, check_for_file: bool| {
@@ -580,451 +2236,2800 @@ impl Configuration {
     }
 }
 
-fn main() -> Result<(), io::Error> {
-    let configuration = Rc::new(Configuration::new());
-    //app global state
-    let mut app_state = GlobalState::new(configuration.clone());
-    app_state.set_tab_titles(vec![
-        "Home".to_string(),
-        "Sources".to_string(),
-        "Settings".to_string(),
-    ]);
-    //source tab state
-    let mut source_tab_state = SourceTabState::new(configuration.clone());
-    source_tab_state.load_sources();
-    //home tab state
-    let mut home_tab_state = HomeTabState::new(configuration.clone());
-    let sources_pathbufs = source_tab_state
-        .source_db
-        .sources
-        .iter()
-        .map(|s| PathBuf::from(s))
-        .collect();
-    home_tab_state.load_data(sources_pathbufs);
-    // thread::sleep(Duration::from_secs(3));
+// searchable fields of every item in the scope
+fn search_candidates(
+    scope: SearchScope,
+    home_tab_state: &HomeTabState,
+    source_tab_state: &SourceTabState,
+) -> Vec<(SearchHit, Vec<String>)> {
+    let song_fields = |song: &Song| {
+        vec![
+            song.name.clone(),
+            song.title.clone().unwrap_or_default(),
+            song.artist.clone().unwrap_or_default(),
+            song.album.clone().unwrap_or_default(),
+            song.path.to_string_lossy().to_string(),
+        ]
+    };
+    match scope {
+        SearchScope::Playlists => home_tab_state
+            .playlists
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (SearchHit::Playlist(i), vec![p.name.clone()]))
+            .collect(),
+        SearchScope::Songs(playlist_idx) => home_tab_state
+            .playlists
+            .get(playlist_idx)
+            .map(|p| {
+                p.songs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, s)| (SearchHit::Song(playlist_idx, i), song_fields(s)))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        SearchScope::Sources => source_tab_state
+            .source_db
+            .sources
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let label = s.label.clone().unwrap_or_default();
+                (SearchHit::Source(i), vec![s.path.clone(), label])
+            })
+            .collect(),
+        SearchScope::Library => home_tab_state
+            .playlists
+            .iter()
+            .enumerate()
+            .flat_map(|(playlist_idx, p)| {
+                p.songs
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, s)| (SearchHit::Song(playlist_idx, i), song_fields(s)))
+            })
+            .collect(),
+    }
+}
 
-    //main
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+// the selection a search starts from
+fn current_search_origin(
+    app_state: &GlobalState,
+    home_tab_state: &HomeTabState,
+    source_tab_state: &SourceTabState,
+) -> Option<SearchHit> {
+    match app_state.selected_tab_idx {
+        Some(HOME_TAB) => {
+            let playlist_idx = home_tab_state.playlists_state.selected()?;
+            match home_tab_state.focus {
+                HomeTabStateFocus::Mid => home_tab_state.songs_list_states[playlist_idx]
+                    .selected()
+                    .map(|song_idx| SearchHit::Song(playlist_idx, song_idx)),
+                _ => Some(SearchHit::Playlist(playlist_idx)),
+            }
+        }
+        Some(SOURCES_TAB) => source_tab_state
+            .sources_list_state
+            .selected()
+            .map(SearchHit::Source),
+        _ => None,
+    }
+}
 
-    let (tx, rx) = mpsc::channel();
+// rerun the search with the typed query and jump to the best hit,
+// an empty query goes back to where the search started
+fn refresh_search(
+    app_state: &mut GlobalState,
+    home_tab_state: &mut HomeTabState,
+    source_tab_state: &mut SourceTabState,
+) {
+    let query = app_state.input.text();
+    let (hit, origin_tab_idx) = match app_state.search.as_mut() {
+        Some(search) if query.is_empty() => {
+            search.hits.clear();
+            search.current = 0;
+            (search.origin, search.origin_tab_idx)
+        }
+        Some(search) => {
+            let candidates = search_candidates(search.scope, home_tab_state, source_tab_state);
+            search.update(&query, candidates);
+            (search.current_hit(), None)
+        }
+        None => return,
+    };
+    if let Some(hit) = hit {
+        apply_search_hit(hit, app_state, home_tab_state, source_tab_state);
+    }
+    if let Some(origin_tab_idx) = origin_tab_idx {
+        app_state.set_selected_tab_idx(origin_tab_idx);
+    }
+}
 
-    //Use input event thread to listen key event and send to ui thread
-    thread::spawn(move || loop {
-        if (event::poll(Duration::from_millis(100)).unwrap()) {
-            let ev = event::read().unwrap();
-            match ev {
-                Event::Key(key) => {
-                    tx.send(InputEvent::Input(key)).unwrap();
-                }
-                _ => {}
+// jump to the hit, switching to the tab it lives in
+fn apply_search_hit(
+    hit: SearchHit,
+    app_state: &mut GlobalState,
+    home_tab_state: &mut HomeTabState,
+    source_tab_state: &mut SourceTabState,
+) {
+    match hit {
+        SearchHit::Playlist(playlist_idx) => {
+            app_state.set_selected_tab_idx(HOME_TAB);
+            home_tab_state.select_playlist(playlist_idx);
+        }
+        SearchHit::Song(playlist_idx, song_idx) => {
+            app_state.set_selected_tab_idx(HOME_TAB);
+            home_tab_state.select_song(playlist_idx, song_idx);
+        }
+        SearchHit::Source(source_idx) => {
+            app_state.set_selected_tab_idx(SOURCES_TAB);
+            if source_idx < source_tab_state.source_db.sources.len() {
+                source_tab_state.sources_list_state.select(Some(source_idx));
             }
-        } else {
-            tx.send(InputEvent::Tick).unwrap();
         }
-    });
+    }
+}
 
-    loop {
-        let input_event = rx.recv().unwrap();
-        match app_state.input_mode {
-            InputMode::Normal => match input_event {
-                InputEvent::Input(key) => match key {
-                    KeyEvent {
-                        code: KeyCode::Char('q'),
-                        modifiers: KeyModifiers::CONTROL,
-                    } => break,
-                    KeyEvent {
-                        code: KeyCode::Char('h'),
-                        modifiers: KeyModifiers::NONE,
-                    } => {
-                        app_state.go_previous_tab();
+// the candidates for the end of a line, with the byte offset they replace it from
+type Completions = fn(&str) -> (usize, Vec<String>);
+
+// a tab of the app, the actions that are not global go to the selected tab
+trait TabHandler {
+    // what the tab does for its focused view, only these actions are passed to handle_action,
+    // the help overlay and the hint bar list them
+    fn actions(&self) -> Vec<(Action, &'static str)>;
+    // false if the tab does nothing for the action
+    fn handle_action(&mut self, action: Action, app_state: &mut GlobalState) -> bool;
+    // the text of the edit popup opened by the tab
+    fn submit_input(&mut self, _input: String, _app_state: &mut GlobalState) {}
+    // what '/' searches in the tab
+    fn search_scope(&self) -> Option<SearchScope> {
+        None
+    }
+    // what tab completes in the edit popup
+    fn input_completions(&self) -> Option<Completions> {
+        None
+    }
+    // a line under the input of the edit popup, and whether the input is good
+    fn input_status(&mut self, _input: &str, _settings: &Settings) -> Option<(String, bool)> {
+        None
+    }
+}
+
+impl TabHandler for HomeTabState {
+    fn actions(&self) -> Vec<(Action, &'static str)> {
+        let mut actions = match self.focus {
+            HomeTabStateFocus::Left => vec![
+                (Action::Open, "Open playlist"),
+                (Action::SelectNext, "Next playlist"),
+                (Action::SelectPrevious, "Previous playlist"),
+                (Action::Play, "Pause"),
+            ],
+            // with marked songs the song actions apply to all of them
+            HomeTabStateFocus::Mid if self.has_marks() => vec![
+                (Action::Play, "Play marked"),
+                (Action::Enqueue, "Queue marked"),
+                (Action::Back, "Clear marks"),
+                (Action::SelectNext, "Next song"),
+                (Action::SelectPrevious, "Previous song"),
+                (Action::ToggleMark, "Mark song"),
+                (Action::MarkRange, "Mark range"),
+                (Action::MarkAll, "Mark all"),
+                (Action::AddToPlaylist, "Add marked to playlist"),
+                (Action::RemoveFromPlaylist, "Remove marked from playlist"),
+                (Action::EditTags, "Edit tags of marked"),
+                (Action::ToggleFavorite, "Toggle favorite"),
+                (Action::Rate(0), "Clear rating"),
+                (Action::Rate(1), "Rate 1 star"),
+                (Action::Rate(2), "Rate 2 stars"),
+                (Action::Rate(3), "Rate 3 stars"),
+                (Action::Rate(4), "Rate 4 stars"),
+                (Action::Rate(5), "Rate 5 stars"),
+            ],
+            HomeTabStateFocus::Mid => vec![
+                (Action::Play, "Play song"),
+                (Action::Enqueue, "Queue song"),
+                (Action::Back, "Back to playlists"),
+                (Action::SelectNext, "Next song"),
+                (Action::SelectPrevious, "Previous song"),
+                (Action::ToggleMark, "Mark song"),
+                (Action::MarkRange, "Mark range"),
+                (Action::MarkAll, "Mark all"),
+                (Action::AddToPlaylist, "Add to playlist"),
+                (Action::RemoveFromPlaylist, "Remove from playlist"),
+                (Action::EditTags, "Edit tags"),
+                (Action::ToggleFavorite, "Toggle favorite"),
+                (Action::Rate(0), "Clear rating"),
+                (Action::Rate(1), "Rate 1 star"),
+                (Action::Rate(2), "Rate 2 stars"),
+                (Action::Rate(3), "Rate 3 stars"),
+                (Action::Rate(4), "Rate 4 stars"),
+                (Action::Rate(5), "Rate 5 stars"),
+            ],
+            HomeTabStateFocus::Duplicates => vec![
+                (Action::Confirm, "Keep this copy"),
+                (Action::Back, "Close duplicates"),
+                (Action::SelectNext, "Next copy"),
+                (Action::SelectPrevious, "Previous copy"),
+            ],
+            HomeTabStateFocus::RecentlyPlayed => vec![
+                (Action::Play, "Replay"),
+                (Action::Enqueue, "Queue"),
+                (Action::Back, "Close recently played"),
+                (Action::SelectNext, "Next entry"),
+                (Action::SelectPrevious, "Previous entry"),
+            ],
+        };
+        actions.extend([
+            (Action::RecentlyPlayed, "Recently played"),
+            (Action::FindDuplicates, "Find duplicates"),
+            (Action::CycleSort, "Sort by next column"),
+            (Action::ToggleSortDirection, "Flip sort direction"),
+        ]);
+        actions
+    }
+    fn handle_action(&mut self, action: Action, app_state: &mut GlobalState) -> bool {
+        match (action, &self.focus) {
+            (Action::Open, HomeTabStateFocus::Left) => self.enter_current_playlist_songs_list(),
+            // the first back only clears the marks
+            (Action::Back, HomeTabStateFocus::Mid) if self.has_marks() => self.clear_marks(),
+            (Action::Back, HomeTabStateFocus::Mid) => self.back_to_playlists_list(),
+            (Action::Back, HomeTabStateFocus::Duplicates) => self.close_duplicates_view(),
+            (Action::Back, HomeTabStateFocus::RecentlyPlayed) => self.close_recently_played(),
+            (Action::SelectNext, HomeTabStateFocus::Left) => self.select_next_playlist(),
+            (Action::SelectNext, HomeTabStateFocus::Mid) => self.select_next_song(),
+            (Action::SelectNext, HomeTabStateFocus::Duplicates) => {
+                if let Some(view) = self.duplicates_view.as_mut() {
+                    view.select_next();
+                }
+            }
+            (Action::SelectNext, HomeTabStateFocus::RecentlyPlayed) => {
+                self.select_next_recently_played()
+            }
+            (Action::SelectPrevious, HomeTabStateFocus::Left) => self.select_previous_playlist(),
+            (Action::SelectPrevious, HomeTabStateFocus::Mid) => self.select_previous_song(),
+            (Action::SelectPrevious, HomeTabStateFocus::Duplicates) => {
+                if let Some(view) = self.duplicates_view.as_mut() {
+                    view.select_previous();
+                }
+            }
+            (Action::SelectPrevious, HomeTabStateFocus::RecentlyPlayed) => {
+                self.select_previous_recently_played()
+            }
+            (Action::Play, HomeTabStateFocus::Left) => app_state.playback.pause(),
+            // the marked songs become the playing list
+            (Action::Play, HomeTabStateFocus::Mid) if self.has_marks() => {
+                let songs = self.clone_target_songs();
+                if let Some(song) = songs.first().cloned() {
+                    app_state.playback.set_playing_list(songs, self.library());
+                    app_state.playback.inqueue(song);
+                    app_state.playback.play();
+                }
+            }
+            (Action::Play, HomeTabStateFocus::Mid) => {
+                if let Some(song) = self.clone_current_selected_song() {
+                    app_state
+                        .playback
+                        .set_playing_list(self.clone_current_playlist_songs(), self.library());
+                    app_state.playback.inqueue(song);
+                    app_state.playback.play();
+                }
+            }
+            (Action::Play, HomeTabStateFocus::RecentlyPlayed) => {
+                if let Some(song) = self.clone_current_selected_recently_played_song() {
+                    app_state.playback.play_now(song);
+                }
+            }
+            (Action::Enqueue, HomeTabStateFocus::Mid) => {
+                let songs = self.clone_target_songs();
+                let count = songs.len();
+                for song in songs {
+                    app_state.playback.inqueue(song);
+                }
+                if count > 1 {
+                    app_state
+                        .notifications
+                        .info(format!("Queued {} songs", count));
+                }
+            }
+            (Action::Enqueue, HomeTabStateFocus::RecentlyPlayed) => {
+                if let Some(song) = self.clone_current_selected_recently_played_song() {
+                    app_state.playback.inqueue(song);
+                }
+            }
+            (Action::Rate(rating), HomeTabStateFocus::Mid) => {
+                match self.rate_target_songs(rating) {
+                    Ok(count) if count > 1 => app_state
+                        .notifications
+                        .info(format!("Rated {} songs {} stars", count, rating)),
+                    Ok(_) => {}
+                    Err(error) => app_state
+                        .notifications
+                        .error(format!("Cannot save stats: {}", error)),
+                }
+            }
+            (Action::ToggleFavorite, HomeTabStateFocus::Mid) => {
+                match self.toggle_target_songs_favorite() {
+                    Ok((count, favorite)) if count > 1 => app_state.notifications.info(format!(
+                        "{} {} songs",
+                        if favorite { "Favorited" } else { "Unfavorited" },
+                        count
+                    )),
+                    Ok(_) => {}
+                    Err(error) => app_state
+                        .notifications
+                        .error(format!("Cannot save stats: {}", error)),
+                }
+            }
+            (Action::ToggleMark, HomeTabStateFocus::Mid) => {
+                self.toggle_mark_current_selected_song()
+            }
+            (Action::MarkRange, HomeTabStateFocus::Mid) => self.toggle_mark_range(),
+            (Action::MarkAll, HomeTabStateFocus::Mid) => self.toggle_mark_all(),
+            (Action::AddToPlaylist, HomeTabStateFocus::Mid) => {
+                if !self.target_song_indices().is_empty() {
+                    self.editing = SongEdit::AddToPlaylist;
+                    app_state.switch_mode_to_edit("Add to playlist:");
+                }
+            }
+            (Action::RemoveFromPlaylist, HomeTabStateFocus::Mid) => {
+                match self.remove_target_songs_from_playlist() {
+                    Ok(0) => {}
+                    Ok(removed) => {
+                        // song indices changed under the hits
+                        app_state.search = None;
+                        app_state
+                            .notifications
+                            .info(format!("Removed {} songs from the playlist", removed));
                     }
-                    KeyEvent {
-                        code: KeyCode::Char('l'),
-                        modifiers: KeyModifiers::NONE,
-                    } => {
-                        app_state.go_next_tab();
+                    Err(error) => app_state.notifications.error(error),
+                }
+            }
+            (Action::EditTags, HomeTabStateFocus::Mid) => {
+                let songs = self.clone_target_songs();
+                if songs.iter().any(|s| tags::is_editable(&s.path)) {
+                    let shared = self.shared_tags();
+                    self.editing = SongEdit::Tags;
+                    app_state.switch_mode_to_edit(
+                        "Tags, as artist=...; album=...; title=...; track=...:",
+                    );
+                    app_state.input.set(&shared);
+                } else if !songs.is_empty() {
+                    app_state
+                        .notifications
+                        .error("only the tags of mp3 files can be edited".to_string());
+                }
+            }
+            (Action::RecentlyPlayed, _) => self.open_recently_played(),
+            (Action::FindDuplicates, _) => self.open_duplicates_view(),
+            (Action::Confirm, HomeTabStateFocus::Duplicates) => {
+                match self.keep_selected_duplicate() {
+                    Ok(Some((kept, replaced))) => app_state.notifications.info(format!(
+                        "Kept {}, it replaces {} copies in the playlists",
+                        kept.path.to_string_lossy(),
+                        replaced
+                    )),
+                    Ok(None) => {}
+                    Err(error) => app_state
+                        .notifications
+                        .error(format!("Cannot save playlists: {}", error)),
+                }
+                // song indices changed under the hits
+                app_state.search = None;
+            }
+            (Action::CycleSort, _) => {
+                if let Err(error) = self.cycle_sort_column() {
+                    app_state
+                        .notifications
+                        .error(format!("Cannot save playlists: {}", error));
+                }
+                app_state.search = None;
+            }
+            (Action::ToggleSortDirection, _) => {
+                if let Err(error) = self.toggle_sort_direction() {
+                    app_state
+                        .notifications
+                        .error(format!("Cannot save playlists: {}", error));
+                }
+                app_state.search = None;
+            }
+            _ => return false,
+        }
+        true
+    }
+    fn submit_input(&mut self, input: String, app_state: &mut GlobalState) {
+        match self.editing {
+            SongEdit::AddToPlaylist if input.trim().is_empty() => {}
+            SongEdit::AddToPlaylist => match self.add_target_songs_to_playlist(&input) {
+                Ok((name, added)) => {
+                    // a new playlist moves the playlist indices under the hits
+                    app_state.search = None;
+                    app_state
+                        .notifications
+                        .info(format!("Added {} songs to {}", added, name));
+                }
+                Err(error) => app_state.notifications.error(error),
+            },
+            SongEdit::Tags => match tags::parse(&input) {
+                Ok(changes) if changes.is_empty() => {}
+                Ok(changes) => {
+                    let (tagged, problems) = self.tag_target_songs(&changes);
+                    for problem in problems {
+                        app_state.notifications.error(problem);
                     }
-                    KeyEvent {
-                        code: KeyCode::Char('i'),
-                        modifiers: KeyModifiers::NONE,
-                    } => match app_state.selected_tab_idx {
-                        Some(idx) => match idx {
-                            0 => match home_tab_state.focus {
-                                HomeTabStateFocus::Left => {
-                                    home_tab_state.enter_current_playlist_songs_list();
-                                }
-                                HomeTabStateFocus::Mid => {}
-                                HomeTabStateFocus::Right => {}
-                            },
-                            _ => {}
-                        },
-                        None => {}
-                    },
-                    KeyEvent {
-                        code: KeyCode::Char('b'),
-                        modifiers: KeyModifiers::NONE,
-                    } => match app_state.selected_tab_idx {
-                        Some(idx) => match idx {
-                            0 => match home_tab_state.focus {
-                                HomeTabStateFocus::Left => {}
-                                HomeTabStateFocus::Mid => home_tab_state.back_to_playlists_list(),
-                                HomeTabStateFocus::Right => {}
-                            },
-                            _ => {}
-                        },
-                        None => {}
-                    },
-                    KeyEvent {
-                        code: KeyCode::Char('j'),
-                        modifiers: KeyModifiers::NONE,
-                    } => match app_state.selected_tab_idx {
-                        Some(idx) => match idx {
-                            0 => match home_tab_state.focus {
-                                HomeTabStateFocus::Left => {
-                                    home_tab_state.select_next_playlist();
-                                }
-                                HomeTabStateFocus::Mid => {
-                                    home_tab_state.select_next_song();
-                                }
-                                HomeTabStateFocus::Right => {}
-                            },
-                            //source tab
-                            1 => {
-                                source_tab_state.select_next();
-                            }
-                            _ => {}
-                        },
-                        None => {}
-                    },
-                    KeyEvent {
-                        code: KeyCode::Char('k'),
-                        modifiers: KeyModifiers::NONE,
-                    } => match app_state.selected_tab_idx {
-                        Some(idx) => match idx {
-                            0 => match home_tab_state.focus {
-                                HomeTabStateFocus::Left => {
-                                    home_tab_state.select_previous_playlist();
-                                }
-                                HomeTabStateFocus::Mid => {
-                                    home_tab_state.select_previous_song();
-                                }
-                                HomeTabStateFocus::Right => {}
-                            },
-                            1 => {
-                                source_tab_state.select_previous();
-                            }
-                            _ => {}
-                        },
-                        None => {}
-                    },
-                    KeyEvent {
-                        code: KeyCode::Char('a'),
-                        modifiers: KeyModifiers::NONE,
-                    } => match app_state.selected_tab_idx {
-                        Some(idx) => match idx {
-                            0 => {}
-                            1 => {
-                                app_state.switch_mode_to_edit();
-                                terminal.show_cursor()?;
+                    // sorted playlists may have moved the songs under the hits
+                    app_state.search = None;
+                    app_state
+                        .notifications
+                        .info(format!("Tagged {} songs", tagged));
+                }
+                Err(error) => app_state.notifications.error(error),
+            },
+        }
+    }
+    fn input_status(&mut self, input: &str, _settings: &Settings) -> Option<(String, bool)> {
+        if input.trim().is_empty() {
+            return None;
+        }
+        let songs = self.clone_target_songs();
+        match self.editing {
+            SongEdit::AddToPlaylist => Some(match self.find_playlist_for_adding(input) {
+                Ok(Some(idx)) => (
+                    format!("adds {} songs to {}", songs.len(), self.playlists[idx].name),
+                    true,
+                ),
+                Ok(None) => (
+                    format!(
+                        "creates playlist {} with {} songs",
+                        input.trim(),
+                        songs.len()
+                    ),
+                    true,
+                ),
+                Err(error) => (error, false),
+            }),
+            SongEdit::Tags => match tags::parse(input) {
+                Ok(changes) if changes.is_empty() => None,
+                Ok(changes) => {
+                    let editable = songs.iter().filter(|s| tags::is_editable(&s.path)).count();
+                    let names: Vec<&str> = changes.iter().map(|(f, _)| f.name()).collect();
+                    let mut status = format!("sets {} of {} songs", names.join(", "), editable);
+                    if editable < songs.len() {
+                        status.push_str(&format!(
+                            ", {} that are not mp3 are left out",
+                            songs.len() - editable
+                        ));
+                    }
+                    Some((status, true))
+                }
+                Err(error) => Some((error, false)),
+            },
+        }
+    }
+    fn search_scope(&self) -> Option<SearchScope> {
+        match self.focus {
+            HomeTabStateFocus::Left => Some(SearchScope::Playlists),
+            HomeTabStateFocus::Mid => self.playlists_state.selected().map(SearchScope::Songs),
+            HomeTabStateFocus::Duplicates | HomeTabStateFocus::RecentlyPlayed => None,
+        }
+    }
+}
+
+impl TabHandler for SourceTabState {
+    fn actions(&self) -> Vec<(Action, &'static str)> {
+        if self.browser.is_some() {
+            return vec![
+                (Action::Confirm, "Open folder"),
+                (Action::Back, "Parent folder"),
+                (Action::AddSource, "Add as source"),
+                (Action::SelectNext, "Next entry"),
+                (Action::SelectPrevious, "Previous entry"),
+                (Action::Play, "Play file"),
+                (Action::Enqueue, "Queue file"),
+                (Action::BrowseFolders, "Close browser"),
+            ];
+        }
+        vec![
+            (Action::AddSource, "Add source"),
+            (Action::BrowseFolders, "Browse folders"),
+            (Action::DeleteSource, "Delete source"),
+            (Action::SelectNext, "Next source"),
+            (Action::SelectPrevious, "Previous source"),
+            (Action::ToggleSource, "Enable/disable source"),
+            (Action::ToggleRecursive, "Toggle recursive scan"),
+            (Action::EditSourceLabel, "Edit label"),
+            (Action::EditSourceExcludes, "Edit exclude patterns"),
+        ]
+    }
+    fn handle_action(&mut self, action: Action, app_state: &mut GlobalState) -> bool {
+        if let Some(browser) = self.browser.as_mut() {
+            match action {
+                Action::SelectNext => browser.select_next(),
+                Action::SelectPrevious => browser.select_previous(),
+                Action::Back => browser.go_up(),
+                Action::BrowseFolders => self.browser = None,
+                Action::AddSource => {
+                    let folder = browser.target_folder().to_string_lossy().to_string();
+                    match self.add_source(&folder) {
+                        Ok(source) => app_state
+                            .notifications
+                            .info(format!("Added source {}, :rescan to scan it", source)),
+                        Err(error) => app_state.notifications.error(error),
+                    }
+                }
+                // enter opens a folder and plays a file
+                Action::Confirm | Action::Play | Action::Enqueue => {
+                    if action == Action::Confirm && browser.enter() {
+                        return true;
+                    }
+                    let song = browser
+                        .selected()
+                        .filter(|entry| !entry.is_folder)
+                        .and_then(|entry| Song::new(entry.path.clone()));
+                    match (song, action) {
+                        (Some(song), Action::Enqueue) => {
+                            app_state
+                                .notifications
+                                .info(format!("Queued {}", song.display_title()));
+                            app_state.playback.inqueue(song);
+                        }
+                        (Some(song), _) => app_state.playback.play_now(song),
+                        (None, _) => return false,
+                    }
+                }
+                _ => return false,
+            }
+            return true;
+        }
+        match action {
+            Action::BrowseFolders => self.open_browser(),
+            Action::SelectNext => self.select_next(),
+            Action::SelectPrevious => self.select_previous(),
+            Action::AddSource => {
+                self.editing = SourceEdit::Add;
+                app_state.switch_mode_to_edit("Folder path:");
+            }
+            Action::ToggleSource => {
+                if let Some((name, enabled)) = self.toggle_current_selected_source() {
+                    match self.save_sources() {
+                        Ok(()) => {
+                            let state = if enabled { "Enabled" } else { "Disabled" };
+                            app_state
+                                .notifications
+                                .info(format!("{} {}, :rescan to apply", state, name));
+                        }
+                        Err(error) => app_state
+                            .notifications
+                            .error(format!("Cannot save sources: {}", error)),
+                    }
+                }
+            }
+            Action::ToggleRecursive => {
+                if let Some((name, recursive)) = self.toggle_current_selected_source_recursive() {
+                    match self.save_sources() {
+                        Ok(()) => {
+                            let scan = if recursive { "with" } else { "without" };
+                            app_state.notifications.info(format!(
+                                "{} is scanned {} subfolders, :rescan to apply",
+                                name, scan
+                            ));
+                        }
+                        Err(error) => app_state
+                            .notifications
+                            .error(format!("Cannot save sources: {}", error)),
+                    }
+                }
+            }
+            Action::EditSourceLabel => {
+                self.edit_current_selected_source(SourceEdit::Label, app_state)
+            }
+            Action::EditSourceExcludes => {
+                self.edit_current_selected_source(SourceEdit::Excludes, app_state)
+            }
+            Action::DeleteSource => self.request_removal_of_current_selected_source(),
+            _ => return false,
+        }
+        true
+    }
+    fn submit_input(&mut self, input: String, app_state: &mut GlobalState) {
+        match self.editing {
+            SourceEdit::Add if input.is_empty() => {}
+            SourceEdit::Add => match self.add_source(&input) {
+                Ok(source) => app_state
+                    .notifications
+                    .info(format!("Added source {}, :rescan to scan it", source)),
+                Err(error) => app_state.notifications.error(error),
+            },
+            // an empty label shows the path again
+            SourceEdit::Label => {
+                if let Some(source) = self.current_selected_source() {
+                    let label = input.trim();
+                    source.label = Some(label.to_string()).filter(|_| !label.is_empty());
+                    if let Err(error) = self.save_sources() {
+                        app_state
+                            .notifications
+                            .error(format!("Cannot save sources: {}", error));
+                    }
+                }
+            }
+            SourceEdit::Excludes => {
+                if let Some(source) = self.current_selected_source() {
+                    source.excludes = input
+                        .split(',')
+                        .map(|p| p.trim().to_string())
+                        .filter(|p| !p.is_empty())
+                        .collect();
+                    let name = source.display_name().to_string();
+                    match self.save_sources() {
+                        Ok(()) => app_state.notifications.info(format!(
+                            "Changed the excludes of {}, :rescan to apply",
+                            name
+                        )),
+                        Err(error) => app_state
+                            .notifications
+                            .error(format!("Cannot save sources: {}", error)),
+                    }
+                }
+            }
+        }
+    }
+    fn search_scope(&self) -> Option<SearchScope> {
+        match self.browser {
+            Some(_) => None,
+            None => Some(SearchScope::Sources),
+        }
+    }
+    fn input_completions(&self) -> Option<Completions> {
+        match self.editing {
+            SourceEdit::Add => Some(|input| (0, paths::folder_completions(input))),
+            SourceEdit::Label | SourceEdit::Excludes => None,
+        }
+    }
+    fn input_status(&mut self, input: &str, settings: &Settings) -> Option<(String, bool)> {
+        if !matches!(self.editing, SourceEdit::Add) || input.trim().is_empty() {
+            return None;
+        }
+        match &self.path_status {
+            Some((checked, status)) if checked == input => Some(status.clone()),
+            _ => {
+                let status = self.check_path(input, settings);
+                self.path_status = Some((input.to_string(), status.clone()));
+                Some(status)
+            }
+        }
+    }
+}
+
+impl TabHandler for StatsTabState {
+    fn actions(&self) -> Vec<(Action, &'static str)> {
+        vec![(Action::CycleStatsRange, "Change range")]
+    }
+    fn handle_action(&mut self, action: Action, _app_state: &mut GlobalState) -> bool {
+        match action {
+            Action::CycleStatsRange => self.cycle_range(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl TabHandler for SettingsState {
+    fn actions(&self) -> Vec<(Action, &'static str)> {
+        vec![
+            (Action::Confirm, "Change setting"),
+            (Action::SelectNext, "Next setting"),
+            (Action::SelectPrevious, "Previous setting"),
+        ]
+    }
+    fn handle_action(&mut self, action: Action, app_state: &mut GlobalState) -> bool {
+        match action {
+            Action::SelectNext => self.select_next(),
+            Action::SelectPrevious => self.select_previous(),
+            Action::Confirm => {
+                let item = self.current_selected_item();
+                match item.kind() {
+                    SettingKind::Toggle | SettingKind::Cycle => {
+                        let theme_names: Vec<String> =
+                            self.themes.iter().map(|(name, _)| name.clone()).collect();
+                        item.advance(&mut self.settings, &app_state.tab_titles, &theme_names);
+                        if let Err(error) = self.save_settings() {
+                            app_state
+                                .notifications
+                                .error(format!("Cannot save settings: {}", error));
+                        }
+                        self.changed_item = Some(item);
+                    }
+                    SettingKind::Input => {
+                        // start from the current value
+                        app_state.input.set(&item.value_text(&self.settings));
+                        app_state.switch_mode_to_edit(item.title());
+                    }
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+    fn submit_input(&mut self, input: String, app_state: &mut GlobalState) {
+        let item = self.current_selected_item();
+        // invalid input leaves the setting as it was
+        if item.apply_input(&mut self.settings, &input) {
+            if let Err(error) = self.save_settings() {
+                app_state
+                    .notifications
+                    .error(format!("Cannot save settings: {}", error));
+            }
+            self.changed_item = Some(item);
+        } else {
+            app_state
+                .notifications
+                .error(format!("Invalid value for {}: {}", item.title(), input));
+        }
+    }
+}
+
+fn selected_tab<'a>(
+    app_state: &GlobalState,
+    home_tab_state: &'a mut HomeTabState,
+    source_tab_state: &'a mut SourceTabState,
+    stats_tab_state: &'a mut StatsTabState,
+    settings_state: &'a mut SettingsState,
+) -> Option<&'a mut dyn TabHandler> {
+    match app_state.selected_tab_idx {
+        Some(HOME_TAB) => Some(home_tab_state),
+        Some(SOURCES_TAB) => Some(source_tab_state),
+        Some(STATS_TAB) => Some(stats_tab_state),
+        Some(SETTINGS_TAB) => Some(settings_state),
+        _ => None,
+    }
+}
+
+// run an action the way its key would, false if nothing handled it
+fn dispatch_action(
+    action: Action,
+    app_state: &mut GlobalState,
+    home_tab_state: &mut HomeTabState,
+    source_tab_state: &mut SourceTabState,
+    stats_tab_state: &mut StatsTabState,
+    settings_state: &mut SettingsState,
+) -> bool {
+    // global actions first
+    match action {
+        Action::Quit => {
+            if settings_state.settings.confirm_on_quit {
+                app_state.input_mode = InputMode::ConfirmQuit;
+            } else {
+                app_state.should_quit = true;
+            }
+        }
+        Action::NextSong => app_state.playback.next(),
+        Action::Stop => app_state.playback.stop(),
+        Action::CyclePlayMode => app_state.playback.cycle_play_mode(),
+        Action::ToggleMiniPlayer => app_state.mini_player = !app_state.mini_player,
+        Action::Play if app_state.mini_player => app_state.playback.toggle_pause(),
+        // the mini player has no tab to act on
+        _ if app_state.mini_player => return false,
+        Action::Help => app_state.switch_mode_to_help(),
+        Action::MessageLog => app_state.switch_mode_to_message_log(),
+        Action::DismissMessage => app_state.notifications.dismiss(),
+        Action::PreviousTab => app_state.go_previous_tab(),
+        Action::NextTab => app_state.go_next_tab(),
+        Action::CommandLine => app_state.switch_mode_to_command(),
+        Action::Undo | Action::Redo => undo_edit(
+            action == Action::Redo,
+            app_state,
+            home_tab_state,
+            source_tab_state,
+        ),
+        Action::Search | Action::SearchLibrary => {
+            let scope = match action {
+                Action::Search => selected_tab(
+                    app_state,
+                    home_tab_state,
+                    source_tab_state,
+                    stats_tab_state,
+                    settings_state,
+                )
+                .and_then(|tab| tab.search_scope()),
+                _ => Some(SearchScope::Library),
+            };
+            let scope = match scope {
+                Some(scope) => scope,
+                None => return false,
+            };
+            let origin = current_search_origin(app_state, home_tab_state, source_tab_state);
+            app_state.search = Some(Search::new(scope, app_state.selected_tab_idx, origin));
+            app_state.switch_mode_to_search();
+        }
+        Action::NextMatch | Action::PreviousMatch => {
+            let hit = app_state.search.as_mut().and_then(|s| match action {
+                Action::NextMatch => s.next_hit(),
+                _ => s.previous_hit(),
+            });
+            match hit {
+                Some(hit) => apply_search_hit(hit, app_state, home_tab_state, source_tab_state),
+                None => return false,
+            }
+        }
+        _ => {
+            return match selected_tab(
+                app_state,
+                home_tab_state,
+                source_tab_state,
+                stats_tab_state,
+                settings_state,
+            ) {
+                Some(tab) if tab.actions().iter().any(|(a, _)| *a == action) => {
+                    tab.handle_action(action, app_state)
+                }
+                _ => false,
+            };
+        }
+    }
+    true
+}
+
+// clicks select, double clicks open, play or confirm, the wheel moves the selection of the list under it
+fn handle_mouse(
+    mouse: MouseEvent,
+    mouse_map: &mut MouseMap,
+    app_state: &mut GlobalState,
+    home_tab_state: &mut HomeTabState,
+    source_tab_state: &mut SourceTabState,
+    stats_tab_state: &mut StatsTabState,
+    settings_state: &mut SettingsState,
+) {
+    let target = match mouse_map.target_at(mouse.column, mouse.row) {
+        Some(target) => target,
+        None => return,
+    };
+    let action = match (mouse.kind, target) {
+        (MouseEventKind::Down(MouseButton::Left), MouseTarget::Tab(tab_idx)) => {
+            app_state.set_selected_tab_idx(tab_idx);
+            None
+        }
+        (MouseEventKind::Down(MouseButton::Left), MouseTarget::Progress(ratio)) => {
+            let duration = app_state
+                .playback
+                .now_playing()
+                .and_then(|n| n.song.duration);
+            if let Some(duration) = duration {
+                app_state.playback.seek(duration.mul_f64(ratio));
+            }
+            None
+        }
+        (MouseEventKind::Down(MouseButton::Left), MouseTarget::Row(kind, idx)) => {
+            let double_click = mouse_map.is_double_click(mouse.column, mouse.row);
+            match kind {
+                ListKind::Playlists => home_tab_state.select_playlist(idx),
+                ListKind::Songs(playlist_idx) => home_tab_state.select_song(playlist_idx, idx),
+                ListKind::Sources => source_tab_state.sources_list_state.select(Some(idx)),
+                ListKind::Browser => {
+                    if let Some(browser) = source_tab_state.browser.as_mut() {
+                        browser.select(idx);
+                    }
+                }
+                ListKind::Settings => settings_state.settings_list_state.select(Some(idx)),
+                ListKind::RecentlyPlayed => home_tab_state.recently_played_state.select(Some(idx)),
+                ListKind::Duplicates => {
+                    // group headers can not be selected, nor kept
+                    match home_tab_state.duplicates_view.as_mut() {
+                        Some(view) if view.rows[idx].1.is_some() => view.state.select(Some(idx)),
+                        _ => return,
+                    }
+                }
+            }
+            match kind {
+                _ if !double_click => None,
+                ListKind::Playlists => Some(Action::Open),
+                ListKind::Songs(_) | ListKind::RecentlyPlayed => Some(Action::Play),
+                ListKind::Settings | ListKind::Duplicates | ListKind::Browser => {
+                    Some(Action::Confirm)
+                }
+                ListKind::Sources => None,
+            }
+        }
+        (MouseEventKind::ScrollDown | MouseEventKind::ScrollUp, MouseTarget::Row(kind, _)) => {
+            // scroll the list under the mouse, not the focused one
+            match (kind, &home_tab_state.focus) {
+                (ListKind::Playlists, HomeTabStateFocus::Mid) => {
+                    home_tab_state.back_to_playlists_list()
+                }
+                (ListKind::Songs(_), HomeTabStateFocus::Left) => {
+                    home_tab_state.enter_current_playlist_songs_list()
+                }
+                _ => {}
+            }
+            match mouse.kind {
+                MouseEventKind::ScrollDown => Some(Action::SelectNext),
+                _ => Some(Action::SelectPrevious),
+            }
+        }
+        _ => None,
+    };
+    if let Some(action) = action {
+        dispatch_action(
+            action,
+            app_state,
+            home_tab_state,
+            source_tab_state,
+            stats_tab_state,
+            settings_state,
+        );
+    }
+}
+
+// the actions dispatch_action handles before the tab, for the help overlay
+fn global_actions(app_state: &GlobalState, searchable: bool) -> Vec<(Action, &'static str)> {
+    let mut actions = vec![
+        (Action::Help, "Help"),
+        (Action::Quit, "Quit"),
+        (Action::PreviousTab, "Previous tab"),
+        (Action::NextTab, "Next tab"),
+        (Action::NextSong, "Next song"),
+        (Action::Stop, "Stop"),
+        (Action::CyclePlayMode, "Change play mode"),
+        (Action::ToggleMiniPlayer, "Mini player"),
+        (Action::CommandLine, "Command line"),
+        (Action::MessageLog, "Messages"),
+        (Action::DismissMessage, "Dismiss message"),
+        (Action::Undo, "Undo"),
+        (Action::Redo, "Redo"),
+    ];
+    if searchable {
+        actions.push((Action::Search, "Search"));
+    }
+    actions.push((Action::SearchLibrary, "Search library"));
+    if app_state.search.is_some() {
+        actions.push((Action::NextMatch, "Next match"));
+        actions.push((Action::PreviousMatch, "Previous match"));
+    }
+    actions
+}
+
+// what the undo history compares and puts back
+fn library_snapshot(home_tab_state: &HomeTabState, source_tab_state: &SourceTabState) -> Snapshot {
+    Snapshot {
+        playlists: home_tab_state.saved_playlists(),
+        duplicate_replacements: home_tab_state.duplicate_replacements.clone(),
+        sources: source_tab_state.source_db.sources.clone(),
+    }
+}
+
+// put back what an edit holds, the files first so the playlists keep the songs as they were,
+// returns the values of the tags it overwrote
+fn apply_edit(
+    edit: Edit,
+    app_state: &mut GlobalState,
+    home_tab_state: &mut HomeTabState,
+    source_tab_state: &mut SourceTabState,
+) -> Vec<(PathBuf, Vec<TagChange>)> {
+    let mut overwritten = vec![];
+    let mut retagged = HashSet::new();
+    for (path, changes) in edit.tags {
+        let fields: Vec<TagField> = changes.iter().map(|(field, _)| *field).collect();
+        let previous = tags::read(&path, &fields);
+        match tags::write(&path, &changes) {
+            Ok(()) => {
+                overwritten.push((path.clone(), previous));
+                retagged.insert(path);
+            }
+            Err(e) => app_state
+                .notifications
+                .error(format!("{}: {}", path.to_string_lossy(), e)),
+        }
+    }
+    if !retagged.is_empty() {
+        if let Err(e) = home_tab_state.reload_songs(&retagged) {
+            app_state
+                .notifications
+                .error(format!("Cannot save playlists: {}", e));
+        }
+    }
+    if !edit.playlists.is_empty() || edit.duplicate_replacements.is_some() {
+        if let Err(e) =
+            home_tab_state.restore_playlists(edit.playlists, edit.duplicate_replacements)
+        {
+            app_state
+                .notifications
+                .error(format!("Cannot save playlists: {}", e));
+        }
+    }
+    if let Some(sources) = edit.sources {
+        if let Err(e) = source_tab_state.restore_sources(sources) {
+            app_state
+                .notifications
+                .error(format!("Cannot save sources: {}", e));
+        }
+    }
+    // song and playlist indices changed under the hits
+    app_state.search = None;
+    overwritten
+}
+
+// 'u' undoes the last edit, ctrl-r redoes the last undone one
+fn undo_edit(
+    redo: bool,
+    app_state: &mut GlobalState,
+    home_tab_state: &mut HomeTabState,
+    source_tab_state: &mut SourceTabState,
+) {
+    let edit = if redo {
+        app_state.undo_history.take_redo()
+    } else {
+        app_state.undo_history.take_undo()
+    };
+    let edit = match edit {
+        Some(edit) => edit,
+        None => {
+            app_state.notifications.info(
+                if redo {
+                    "Nothing to redo"
+                } else {
+                    "Nothing to undo"
+                }
+                .to_string(),
+            );
+            return;
+        }
+    };
+    let description = edit.describe();
+    let overwritten_tags = apply_edit(edit, app_state, home_tab_state, source_tab_state);
+    let current = library_snapshot(home_tab_state, source_tab_state);
+    let inverse = Edit::between(app_state.undo_history.current(), &current, overwritten_tags);
+    if redo {
+        app_state.undo_history.redone(inverse, current);
+        app_state
+            .notifications
+            .info(format!("Redid the changes to {}", description));
+    } else {
+        app_state.undo_history.undone(inverse, current);
+        app_state
+            .notifications
+            .info(format!("Undid the changes to {}", description));
+    }
+}
+
+// the library song that best matches the query
+fn best_library_match(
+    query: &str,
+    home_tab_state: &HomeTabState,
+    source_tab_state: &SourceTabState,
+) -> Option<Song> {
+    let mut search = Search::new(SearchScope::Library, None, None);
+    search.update(
+        query,
+        search_candidates(SearchScope::Library, home_tab_state, source_tab_state),
+    );
+    match search.current_hit()? {
+        SearchHit::Song(playlist_idx, song_idx) => {
+            Some(home_tab_state.playlists[playlist_idx].songs[song_idx].clone())
+        }
+        _ => None,
+    }
+}
+
+// run a `:` command line, Ok with a message for the status line if there is one
+fn run_command(
+    line: &str,
+    app_state: &mut GlobalState,
+    home_tab_state: &mut HomeTabState,
+    source_tab_state: &mut SourceTabState,
+    stats_tab_state: &mut StatsTabState,
+    settings_state: &mut SettingsState,
+) -> Result<Option<String>, String> {
+    match Command::parse(line)? {
+        Command::Play(None) => app_state.playback.play(),
+        Command::Play(Some(query)) => {
+            let song = best_library_match(&query, home_tab_state, source_tab_state)
+                .ok_or_else(|| format!("no song matches {}", query))?;
+            let message = format!("Playing {}", song.display_title());
+            app_state.playback.play_now(song);
+            return Ok(Some(message));
+        }
+        Command::Queue(query) => {
+            let song = best_library_match(&query, home_tab_state, source_tab_state)
+                .ok_or_else(|| format!("no song matches {}", query))?;
+            let message = format!("Queued {}", song.display_title());
+            app_state.playback.inqueue(song);
+            return Ok(Some(message));
+        }
+        Command::NewPlaylist(name) => {
+            home_tab_state.create_playlist(name.clone())?;
+            // playlist indices changed under the hits
+            app_state.search = None;
+            return Ok(Some(format!("Created playlist {}", name)));
+        }
+        Command::AddSource(path) => {
+            let source = source_tab_state.add_source(&path)?;
+            return Ok(Some(format!("Added source {}, :rescan to scan it", source)));
+        }
+        Command::Seek(position) => app_state.playback.seek(position),
+        Command::Volume(volume) => app_state.playback.set_volume(volume),
+        Command::Mode(play_mode) => app_state.playback.set_play_mode(play_mode),
+        Command::Rescan => {
+            let scan_results = home_tab_state.rescan(
+                &source_tab_state.source_db.sources,
+                &settings_state.settings,
+            );
+            let recorded = source_tab_state.record_scan(scan_results);
+            app_state.search = None;
+            // the edits were made to the library before the scan
+            app_state
+                .undo_history
+                .reset(library_snapshot(home_tab_state, source_tab_state));
+            let songs_count = home_tab_state
+                .library()
+                .iter()
+                .map(|s| &s.path)
+                .collect::<HashSet<_>>()
+                .len();
+            recorded.map_err(|e| format!("Cannot save sources: {}", e))?;
+            return Ok(Some(format!("Scanned {} songs", songs_count)));
+        }
+        Command::Action(action) => {
+            let handled = dispatch_action(
+                action,
+                app_state,
+                home_tab_state,
+                source_tab_state,
+                stats_tab_state,
+                settings_state,
+            );
+            if !handled {
+                return Err(format!("{} does nothing here", action.name()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+// settings that take effect right away, the others are read when needed
+fn apply_setting(
+    item: SettingItem,
+    settings_state: &SettingsState,
+    app_state: &mut GlobalState,
+    home_tab_state: &mut HomeTabState,
+    source_tab_state: &mut SourceTabState,
+) {
+    let settings = &settings_state.settings;
+    match item {
+        SettingItem::PlayMode => app_state.playback.set_play_mode(settings.play_mode),
+        SettingItem::Volume => app_state.playback.set_volume(settings.volume),
+        SettingItem::SongColumns => home_tab_state.song_columns = settings.song_columns.clone(),
+        SettingItem::Theme => app_state.theme = settings_state.theme(),
+        SettingItem::ScanRecursive => source_tab_state.scan_recursive = settings.scan_recursive,
+        SettingItem::ScanExtensions => {
+            source_tab_state.scan_extensions = settings.scan_extensions.clone()
+        }
+        _ => {}
+    }
+}
+
+// this terminal backend has no bracketed paste, pasted text comes in as keys all at once,
+// so typed keys that are already waiting behind the first one are taken as a paste
+// fewer keys than this in one read were typed fast, not pasted
+const PASTE_MIN_KEYS: usize = 16;
+
+fn read_input_events(event: Event) -> Vec<InputEvent<KeyEvent>> {
+    let typed = |key: &KeyEvent| match (key.code, key.modifiers) {
+        (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => Some(c),
+        (KeyCode::Enter, KeyModifiers::NONE) => Some('\n'),
+        _ => None,
+    };
+    let key = match event {
+        Event::Key(key) => key,
+        Event::Mouse(mouse) => return vec![InputEvent::Mouse(mouse)],
+        // a tick redraws at the new size right away
+        Event::Resize(..) => return vec![InputEvent::Tick],
+    };
+    let mut keys = vec![key];
+    let mut next_event = None;
+    if typed(&key).is_some() {
+        while event::poll(Duration::ZERO).unwrap() {
+            match event::read().unwrap() {
+                Event::Key(key) if typed(&key).is_some() => keys.push(key),
+                event => {
+                    next_event = Some(event);
+                    break;
+                }
+            }
+        }
+    }
+    let mut input_events = if keys.len() >= PASTE_MIN_KEYS {
+        let text: String = keys.iter().filter_map(typed).collect();
+        // a pasted line that ends with a line break is submitted like a typed one
+        let mut input_events = vec![InputEvent::Paste(text.trim_end_matches('\n').to_string())];
+        if text.ends_with('\n') {
+            input_events.push(InputEvent::Input(KeyEvent::new(
+                KeyCode::Enter,
+                KeyModifiers::NONE,
+            )));
+        }
+        input_events
+    } else {
+        keys.into_iter().map(InputEvent::Input).collect()
+    };
+    if let Some(event) = next_event {
+        input_events.extend(read_input_events(event));
+    }
+    input_events
+}
+
+fn main() -> Result<(), io::Error> {
+    let configuration = Rc::new(Configuration::new());
+    //normal mode keys, report keymap.json problems before the ui takes the terminal
+    let (mut keymap, keymap_problems) = Keymap::load(&configuration.keymap_file_path);
+    if !keymap_problems.is_empty() {
+        println!("Problems in the key bindings:");
+        for problem in keymap_problems.iter() {
+            println!("  {}", problem);
+        }
+        println!("Continue anyway? (y/n)");
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if input.trim().to_lowercase() != "y" {
+            std::process::exit(0);
+        }
+    }
+    //song stats shared with the playback thread
+    let song_stats = Arc::new(Mutex::new(SongStatsDB::load(
+        configuration.stats_file_path.clone(),
+    )));
+    //listening history shared with the playback thread
+    let listening_history = Arc::new(Mutex::new(ListeningHistory::load(
+        configuration.history_file_path.clone(),
+    )));
+    //app global state
+    let mut app_state = GlobalState::new(song_stats.clone(), listening_history.clone());
+    // `songbreeze --mini` starts with only the player, for a small terminal split
+    app_state.mini_player = env::args().skip(1).any(|arg| arg == "--mini");
+    app_state.set_tab_titles(vec![
+        "Home".to_string(),
+        "Sources".to_string(),
+        "Stats".to_string(),
+        "Settings".to_string(),
+    ]);
+    //settings tab state
+    let mut settings_state = SettingsState::new(configuration.clone());
+    for problem in settings_state.load_settings() {
+        app_state.notifications.error(problem);
+    }
+    let startup_tab_idx = app_state
+        .tab_titles
+        .iter()
+        .position(|t| *t == settings_state.settings.startup_tab);
+    if let Some(startup_tab_idx) = startup_tab_idx {
+        app_state.set_selected_tab_idx(startup_tab_idx);
+    }
+    //source tab state
+    let mut source_tab_state = SourceTabState::new(configuration.clone());
+    source_tab_state.load_sources();
+    //home tab state
+    let mut home_tab_state = HomeTabState::new(
+        configuration.clone(),
+        song_stats.clone(),
+        listening_history.clone(),
+    );
+    let scan_results = home_tab_state.load_data(
+        &source_tab_state.source_db.sources,
+        &settings_state.settings,
+    );
+    if let Err(e) = source_tab_state.record_scan(scan_results) {
+        app_state
+            .notifications
+            .error(format!("Cannot save sources: {}", e));
+    }
+    app_state
+        .undo_history
+        .reset(library_snapshot(&home_tab_state, &source_tab_state));
+    let mut recorded_revisions = (
+        home_tab_state.playlists_revision,
+        source_tab_state.sources_revision,
+    );
+    for item in SettingItem::ALL {
+        apply_setting(
+            item,
+            &settings_state,
+            &mut app_state,
+            &mut home_tab_state,
+            &mut source_tab_state,
+        );
+    }
+    //stats tab state
+    let mut stats_tab_state = StatsTabState::new(listening_history.clone());
+    // thread::sleep(Duration::from_secs(3));
+
+    //main, the terminal is given back when the guard drops or something panics
+    term::install_panic_hook(configuration.crash_log_file_path.clone());
+    let _terminal_guard = TerminalGuard::new()?;
+    let backend = CrosstermBackend::new(io::stdout());
+    let mut terminal = Terminal::new(backend)?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut mouse_map = MouseMap::new();
+
+    //Use input event thread to listen key event and send to ui thread
+    thread::spawn(move || loop {
+        let input_events = if event::poll(Duration::from_millis(100)).unwrap() {
+            read_input_events(event::read().unwrap())
+        } else {
+            vec![InputEvent::Tick]
+        };
+        // the ui has quit
+        if input_events.into_iter().any(|e| tx.send(e).is_err()) {
+            break;
+        }
+    });
+
+    loop {
+        let input_event = rx.recv().unwrap();
+        while let Ok(playback_event) = app_state.playback.events.try_recv() {
+            match playback_event {
+                PlaybackEvent::Broken(path, error) => {
+                    app_state.notifications.error(format!(
+                        "Cannot play {}: {}",
+                        path.display(),
+                        error
+                    ));
+                    home_tab_state.broken_songs.insert(path);
+                }
+                PlaybackEvent::NoOutput(error) => {
+                    app_state
+                        .notifications
+                        .error(format!("No audio output, nothing can be played: {}", error));
+                }
+                PlaybackEvent::NotSaved(error) => app_state.notifications.error(error),
+            }
+        }
+        //play counts and ratings may have changed the smart playlists
+        home_tab_state.refresh_smart_playlists_if_stats_changed();
+        home_tab_state.refresh_recently_played_if_history_changed();
+        match app_state.input_mode {
+            InputMode::Normal => match input_event {
+                InputEvent::Input(key) => {
+                    app_state.notifications.dismiss_info();
+                    if let Some(action) = keymap.feed(key) {
+                        dispatch_action(
+                            action,
+                            &mut app_state,
+                            &mut home_tab_state,
+                            &mut source_tab_state,
+                            &mut stats_tab_state,
+                            &mut settings_state,
+                        );
+                    }
+                }
+                InputEvent::Mouse(mouse) => {
+                    app_state.notifications.dismiss_info();
+                    handle_mouse(
+                        mouse,
+                        &mut mouse_map,
+                        &mut app_state,
+                        &mut home_tab_state,
+                        &mut source_tab_state,
+                        &mut stats_tab_state,
+                        &mut settings_state,
+                    );
+                }
+                // pasted text replays as keys, the ones after a key that starts typing are typed
+                InputEvent::Paste(text) => {
+                    app_state.notifications.dismiss_info();
+                    for (i, c) in text.char_indices() {
+                        match app_state.input_mode {
+                            InputMode::Normal => {}
+                            InputMode::Edit | InputMode::Command => {
+                                app_state.input.insert_str(&text[i..]);
+                                break;
                             }
-                            _ => {}
-                        },
-                        None => {}
-                    },
+                            InputMode::Search => {
+                                app_state.input.insert_str(&text[i..]);
+                                refresh_search(
+                                    &mut app_state,
+                                    &mut home_tab_state,
+                                    &mut source_tab_state,
+                                );
+                                break;
+                            }
+                            _ => break,
+                        }
+                        let key = match c {
+                            '\n' => KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+                            c => KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE),
+                        };
+                        if let Some(action) = keymap.feed(key) {
+                            dispatch_action(
+                                action,
+                                &mut app_state,
+                                &mut home_tab_state,
+                                &mut source_tab_state,
+                                &mut stats_tab_state,
+                                &mut settings_state,
+                            );
+                        }
+                    }
+                }
+                InputEvent::Tick => {}
+            },
+            InputMode::Search => match input_event {
+                InputEvent::Input(key) => match key {
                     KeyEvent {
-                        code: KeyCode::Char('d'),
+                        code: KeyCode::Enter,
                         modifiers: KeyModifiers::NONE,
-                    } => match app_state.selected_tab_idx {
-                        Some(idx) => match idx {
-                            0 => {}
-                            1 => {
-                                source_tab_state.delete_current_selected_source();
-                            }
-                            _ => {}
-                        },
-                        None => {}
-                    },
+                    } => {
+                        // keep the hits for 'n'/'N'
+                        if app_state.search.as_ref().is_some_and(|s| s.hits.is_empty()) {
+                            app_state.search = None;
+                        }
+                        app_state.input.clear();
+                        app_state.switch_mode_to_normal();
+                    }
                     KeyEvent {
+                        code: KeyCode::Esc,
+                        modifiers: KeyModifiers::NONE,
+                    } => {
+                        app_state.input.clear();
+                        refresh_search(&mut app_state, &mut home_tab_state, &mut source_tab_state);
+                        app_state.search = None;
+                        app_state.switch_mode_to_normal();
+                    }
+                    KeyEvent {
+                        code: KeyCode::Up,
+                        modifiers: KeyModifiers::NONE,
+                    }
+                    | KeyEvent {
                         code: KeyCode::Char('p'),
+                        modifiers: KeyModifiers::CONTROL,
+                    } => {
+                        if let Some(hit) = app_state.search.as_mut().and_then(|s| s.previous_hit())
+                        {
+                            apply_search_hit(
+                                hit,
+                                &mut app_state,
+                                &mut home_tab_state,
+                                &mut source_tab_state,
+                            );
+                        }
+                    }
+                    KeyEvent {
+                        code: KeyCode::Down,
                         modifiers: KeyModifiers::NONE,
-                    } => match app_state.selected_tab_idx {
-                        Some(idx) => match idx {
-                            0 => match home_tab_state.focus {
-                                HomeTabStateFocus::Left => {
-                                    app_state.playback.pause();
-                                }
-                                HomeTabStateFocus::Mid => {
-                                    let current_song = home_tab_state.clone_current_selected_song();
-                                    if let Some(current_song) = current_song {
-                                        app_state.playback.inqueue(current_song);
-                                        app_state.playback.play();
-                                    }
-                                }
-                                HomeTabStateFocus::Right => {}
-                            },
-                            1 => {}
-                            _ => {}
-                        },
-                        None => {}
-                    },
+                    }
+                    | KeyEvent {
+                        code: KeyCode::Char('n'),
+                        modifiers: KeyModifiers::CONTROL,
+                    } => {
+                        if let Some(hit) = app_state.search.as_mut().and_then(|s| s.next_hit()) {
+                            apply_search_hit(
+                                hit,
+                                &mut app_state,
+                                &mut home_tab_state,
+                                &mut source_tab_state,
+                            );
+                        }
+                    }
+                    key => {
+                        let query = app_state.input.text();
+                        if app_state.input.handle_key(key) && app_state.input.text() != query {
+                            refresh_search(
+                                &mut app_state,
+                                &mut home_tab_state,
+                                &mut source_tab_state,
+                            );
+                        }
+                    }
+                },
+                InputEvent::Paste(text) => {
+                    app_state.input.insert_str(&text);
+                    refresh_search(&mut app_state, &mut home_tab_state, &mut source_tab_state);
+                }
+                InputEvent::Mouse(_) | InputEvent::Tick => {}
+            },
+            InputMode::Help | InputMode::MessageLog => match input_event {
+                InputEvent::Input(key) => match key.code {
+                    KeyCode::Char('j') | KeyCode::Down => app_state.overlay_scroll += 1,
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        app_state.overlay_scroll = app_state.overlay_scroll.saturating_sub(1)
+                    }
+                    _ => app_state.switch_mode_to_normal(),
+                },
+                InputEvent::Paste(_) => app_state.switch_mode_to_normal(),
+                InputEvent::Mouse(mouse) => match mouse.kind {
+                    MouseEventKind::ScrollDown => app_state.overlay_scroll += 1,
+                    MouseEventKind::ScrollUp => {
+                        app_state.overlay_scroll = app_state.overlay_scroll.saturating_sub(1)
+                    }
                     _ => {}
                 },
                 InputEvent::Tick => {}
             },
-            InputMode::Edit => match input_event {
+            InputMode::ConfirmQuit => match input_event {
+                InputEvent::Input(KeyEvent {
+                    code: KeyCode::Char('y'),
+                    ..
+                }) => app_state.should_quit = true,
+                InputEvent::Input(_) | InputEvent::Paste(_) => app_state.switch_mode_to_normal(),
+                InputEvent::Mouse(_) | InputEvent::Tick => {}
+            },
+            InputMode::ConfirmSourceRemoval => match input_event {
+                InputEvent::Input(KeyEvent {
+                    code: KeyCode::Char(c),
+                    ..
+                }) => {
+                    if let Some(removal) = source_tab_state.removal.take() {
+                        // without playlist entries to prune, yes is enough
+                        let prune = match c {
+                            'p' => Some(true),
+                            'k' => Some(false),
+                            'y' if removal.playlist_entries == 0 => Some(false),
+                            _ => None,
+                        };
+                        if let Some(prune) = prune {
+                            let source = source_tab_state.delete_source(removal.source_idx);
+                            let sources_saved = source_tab_state.save_sources();
+                            let playlists_saved =
+                                home_tab_state.remove_source_songs(&removal, prune);
+                            // song indices changed under the hits
+                            app_state.search = None;
+                            let mut message = format!(
+                                "Removed source {}, {} tracks left the library",
+                                source.display_name(),
+                                removal.library_tracks
+                            );
+                            if prune && removal.playlist_entries > 0 {
+                                message.push_str(&format!(
+                                    ", {} playlist entries were pruned",
+                                    removal.playlist_entries
+                                ));
+                            }
+                            app_state.notifications.info(message);
+                            if let Err(e) = sources_saved {
+                                app_state
+                                    .notifications
+                                    .error(format!("Cannot save sources: {}", e));
+                            }
+                            if let Err(e) = playlists_saved {
+                                app_state
+                                    .notifications
+                                    .error(format!("Cannot save playlists: {}", e));
+                            }
+                        }
+                    }
+                    app_state.switch_mode_to_normal();
+                }
+                InputEvent::Input(_) | InputEvent::Paste(_) => {
+                    source_tab_state.removal = None;
+                    app_state.switch_mode_to_normal();
+                }
+                InputEvent::Mouse(_) | InputEvent::Tick => {}
+            },
+            InputMode::Edit | InputMode::Command => match input_event {
                 InputEvent::Input(key) => match key {
                     KeyEvent {
                         code: KeyCode::Enter,
                         modifiers: KeyModifiers::NONE,
                     } => {
-                        // todo: handle input stream before clear
-                        match app_state.selected_tab_idx {
-                            Some(idx) => match idx {
-                                0 => {}
-                                1 => {
-                                    source_tab_state.add_source(
-                                        app_state
-                                            .input_stream
-                                            .iter()
-                                            .map(|s| s.to_string())
-                                            .collect::<String>(),
-                                    );
-                                }
-                                _ => {}
-                            },
-                            None => {}
+                        let input = app_state.input.text();
+                        app_state.input.clear();
+                        if let InputMode::Command = app_state.input_mode {
+                            app_state.switch_mode_to_normal();
+                            app_state.command_history.push(&input);
+                            match run_command(
+                                &input,
+                                &mut app_state,
+                                &mut home_tab_state,
+                                &mut source_tab_state,
+                                &mut stats_tab_state,
+                                &mut settings_state,
+                            ) {
+                                Ok(Some(message)) => app_state.notifications.info(message),
+                                Ok(None) => {}
+                                Err(error) => app_state.notifications.error(error),
+                            }
+                        } else {
+                            app_state.edit_history().push(&input);
+                            if let Some(tab) = selected_tab(
+                                &app_state,
+                                &mut home_tab_state,
+                                &mut source_tab_state,
+                                &mut stats_tab_state,
+                                &mut settings_state,
+                            ) {
+                                tab.submit_input(input, &mut app_state);
+                            }
+                            app_state.switch_mode_to_normal();
                         }
-                        app_state.input_stream.clear();
-                        app_state.switch_mode_to_normal();
-                        terminal.hide_cursor()?;
                     }
                     KeyEvent {
                         code: KeyCode::Esc,
                         modifiers: KeyModifiers::NONE,
                     } => {
-                        app_state.input_stream.clear();
+                        app_state.input.clear();
                         app_state.switch_mode_to_normal();
-                        terminal.hide_cursor()?;
                     }
                     KeyEvent {
-                        code: KeyCode::Char(c),
+                        code: KeyCode::Tab,
                         modifiers: KeyModifiers::NONE,
                     } => {
-                        app_state.input_stream.push(c.to_string());
+                        let completions = match app_state.input_mode {
+                            InputMode::Command => Some(command::completions as Completions),
+                            _ => selected_tab(
+                                &app_state,
+                                &mut home_tab_state,
+                                &mut source_tab_state,
+                                &mut stats_tab_state,
+                                &mut settings_state,
+                            )
+                            .and_then(|tab| tab.input_completions()),
+                        };
+                        if let Some(completions) = completions {
+                            app_state.complete_input(completions);
+                        }
                     }
                     KeyEvent {
-                        code: KeyCode::Backspace,
+                        code: code @ (KeyCode::Up | KeyCode::Down),
                         modifiers: KeyModifiers::NONE,
                     } => {
-                        app_state.input_stream.pop();
+                        app_state.browse_input_history(code == KeyCode::Up);
+                    }
+                    // backspace on an empty command line leaves it
+                    KeyEvent {
+                        code: KeyCode::Backspace,
+                        modifiers: KeyModifiers::NONE,
+                    } if app_state.input.is_empty()
+                        && matches!(app_state.input_mode, InputMode::Command) =>
+                    {
+                        app_state.switch_mode_to_normal();
+                    }
+                    key => {
+                        app_state.input.handle_key(key);
                     }
-                    _ => {}
                 },
-                InputEvent::Tick => {}
+                InputEvent::Paste(text) => app_state.input.insert_str(&text),
+                InputEvent::Mouse(_) | InputEvent::Tick => {}
             },
         }
 
+        if app_state.should_quit {
+            break;
+        }
+        //edits saved by the last action can be undone
+        let revisions = (
+            home_tab_state.playlists_revision,
+            source_tab_state.sources_revision,
+        );
+        if revisions != recorded_revisions || !home_tab_state.tag_undo.is_empty() {
+            recorded_revisions = revisions;
+            let tags = mem::take(&mut home_tab_state.tag_undo);
+            app_state
+                .undo_history
+                .record(library_snapshot(&home_tab_state, &source_tab_state), tags);
+        }
+        //a source removal is confirmed once the playlists told what it takes away
+        if let (InputMode::Normal, Some(removal)) =
+            (&app_state.input_mode, source_tab_state.removal.as_mut())
+        {
+            home_tab_state.count_source_removal(removal);
+            app_state.input_mode = InputMode::ConfirmSourceRemoval;
+        }
+        //settings changed by the last action
+        if let Some(item) = settings_state.changed_item.take() {
+            apply_setting(
+                item,
+                &settings_state,
+                &mut app_state,
+                &mut home_tab_state,
+                &mut source_tab_state,
+            );
+        }
+        //after the actions, a range change or a finished song shows in this draw
+        if let Some(STATS_TAB) = app_state.selected_tab_idx {
+            stats_tab_state.refresh(&home_tab_state);
+        }
+
+        // what the keys do right now, for the hint bar and the help overlay
+        // the edit popup checks its input against the settings
+        let settings = match app_state.input_mode {
+            InputMode::Edit => Some(settings_state.settings.clone()),
+            _ => None,
+        };
+        let (tab_title, tab_actions, searchable, input_status) = match selected_tab(
+            &app_state,
+            &mut home_tab_state,
+            &mut source_tab_state,
+            &mut stats_tab_state,
+            &mut settings_state,
+        ) {
+            Some(tab) => (
+                app_state.tab_titles[app_state.selected_tab_idx.unwrap()].clone(),
+                tab.actions(),
+                tab.search_scope().is_some(),
+                settings
+                    .as_ref()
+                    .and_then(|settings| tab.input_status(&app_state.input.text(), settings)),
+            ),
+            None => (String::new(), vec![], false, None),
+        };
+        let global_actions = global_actions(&app_state, searchable);
+
         terminal.draw(|f| {
-            let boards = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints(
-                    [
-                        Constraint::Percentage(10),
-                        Constraint::Percentage(80),
-                        Constraint::Percentage(10),
-                    ]
-                    .as_ref(),
-                )
-                .split(f.size());
-            let tabs_board = boards[0];
-            let main_board = boards[1];
-            let player_board = boards[2];
+            let theme = app_state.theme;
+            mouse_map.clear();
+            let now_playing = app_state.playback.now_playing();
+            // title, play mode and volume, the same in the player bar and the mini player
+            let now_playing_title = match now_playing.as_ref() {
+                Some(now_playing) if now_playing.is_paused() => {
+                    format!("⏸ {}", now_playing.song.display_title())
+                }
+                Some(now_playing) => format!("▶ {}", now_playing.song.display_title()),
+                None => "■ Not playing".to_string(),
+            };
+            let (progress_ratio, progress_label) = now_playing
+                .as_ref()
+                .map_or((0.0, String::new()), |n| n.progress());
+            let player_status = format!(
+                "{}  Vol {}%",
+                app_state.playback.play_mode().title(),
+                app_state.playback.volume
+            );
+
+            // the mini player: a title line and a progress line at the top of the terminal
+            if app_state.mini_player {
+                let size = f.size();
+                let title_board = Rect::new(size.x, size.y, size.width, size.height.min(1));
+                let title_line = match app_state.input_mode {
+                    InputMode::ConfirmQuit => {
+                        Spans::from(Span::styled("Quit songbreeze? (y/n)", theme.accent))
+                    }
+                    // a one line terminal gets the time on the title line
+                    _ if size.height < 2 => Spans::from(vec![
+                        Span::styled(now_playing_title, theme.text),
+                        Span::styled(
+                            format!("  {}  {}", progress_label, player_status),
+                            theme.muted,
+                        ),
+                    ]),
+                    _ => Spans::from(vec![
+                        Span::styled(now_playing_title, theme.text),
+                        Span::styled(format!("  {}", player_status), theme.muted),
+                    ]),
+                };
+                f.render_widget(Paragraph::new(title_line), title_board);
+                if size.height >= 2 {
+                    let progress_board = Rect::new(size.x, size.y + 1, size.width, 1);
+                    mouse_map.add_progress(progress_board, &progress_label);
+                    let progress = LineGauge::default()
+                        .gauge_style(theme.gauge)
+                        .line_set(symbols::line::THICK)
+                        .label(progress_label)
+                        .ratio(progress_ratio.clamp(0.0, 1.0));
+                    f.render_widget(progress, progress_board);
+                }
+                return;
+            }
+
+            let layout = &settings_state.settings.layout;
+            let screen = ScreenBoards::new(f.size(), layout);
+            let main_board = screen.main;
 
             // draw tabs block and tabs content
             let tab_titles = app_state.cloned_tab_titles();
             let selected_tab_idx = app_state.get_selected_tab_idx().unwrap_or(0);
+            if let Some(tabs_board) = screen.menu {
+                let titles: Vec<Spans> = tab_titles.iter().cloned().map(Spans::from).collect();
+                let tabs_content = Tabs::new(titles)
+                    .style(theme.text)
+                    .highlight_style(theme.highlight)
+                    .select(selected_tab_idx)
+                    .divider(DOT);
+                if screen.compact {
+                    f.render_widget(tabs_content, tabs_board);
+                    mouse_map.add_tabs(tabs_board, &tab_titles);
+                } else {
+                    let tabs_block = Block::default().borders(Borders::ALL).title("Menu");
+                    f.render_widget(tabs_block, tabs_board);
+                    let tabs_content_board = tabs_board.inner(&Margin {
+                        vertical: 1,
+                        horizontal: 5,
+                    });
+                    f.render_widget(tabs_content, tabs_content_board);
+                    mouse_map.add_tabs(tabs_content_board, &tab_titles);
+                }
+            }
 
-            let tabs_block = Block::default().borders(Borders::ALL).title("Menu");
-            f.render_widget(tabs_block, tabs_board);
-            let titles: Vec<Spans> = tab_titles.iter().cloned().map(Spans::from).collect();
-            let tabs_content = Tabs::new(titles)
-                .style(Style::default().fg(Color::White))
-                .highlight_style(Style::default().fg(Color::Yellow))
-                .select(selected_tab_idx)
-                .divider(DOT);
-            let tabs_content_board = Layout::default()
-                .constraints([Constraint::Percentage(10)])
-                .horizontal_margin(5)
-                .split(tabs_board)[0];
-            let tabs_content_board = Rect::new(
-                tabs_content_board.left(),
-                tabs_content_board.bottom() / 2,
-                tabs_content_board.width,
-                tabs_content_board.height - (tabs_content_board.bottom() / 2),
-            );
-            f.render_widget(tabs_content, tabs_content_board);
+            // while typing a query the searched list only shows its hits
+            let filtering_scope = match app_state.input_mode {
+                InputMode::Search if !app_state.input.is_empty() => {
+                    app_state.search.as_ref().map(|s| s.scope)
+                }
+                _ => None,
+            };
+            let hit_style = |hit: SearchHit| {
+                if app_state.search.as_ref().is_some_and(|s| s.contains(hit)) {
+                    theme.search_hit
+                } else {
+                    Style::default()
+                }
+            };
+            let filtered_list_state = || {
+                let mut state = ListState::default();
+                state.select(app_state.search.as_ref().map(|s| s.current));
+                state
+            };
 
             // draw main block and main content corresponde to selected tab
             match selected_tab_idx {
-                //Home
-                0 => {
-                    let main_block = Block::default().borders(Borders::ALL).title("Home");
+                HOME_TAB => {
+                    let current_playlist_idx = home_tab_state.playlists_state.selected().unwrap();
+                    let home_board = main_board.inner(&Margin {
+                        vertical: 1,
+                        horizontal: 1,
+                    });
+                    // the compact layout only shows the focused pane
+                    let (main_left_board, main_mid_board) = match home_tab_state.focus {
+                        _ if !screen.compact => {
+                            let (playlists_board, songs_board) =
+                                screen::split_home(home_board, layout);
+                            (playlists_board, Some(songs_board))
+                        }
+                        HomeTabStateFocus::Left => (Some(home_board), None),
+                        _ => (None, Some(home_board)),
+                    };
+                    // without the playlists pane the title tells which playlist is shown
+                    let mut main_title = match main_left_board {
+                        Some(_) => "Home".to_string(),
+                        None => format!(
+                            "Home: {}",
+                            home_tab_state.playlists[current_playlist_idx].name
+                        ),
+                    };
+                    if home_tab_state.has_marks() {
+                        main_title.push_str(&format!(
+                            " ({} marked)",
+                            home_tab_state.target_song_indices().len()
+                        ));
+                    }
+                    let main_block = Block::default().borders(Borders::ALL).title(main_title);
                     f.render_widget(main_block, main_board);
 
-                    let main_board = Layout::default()
-                        .margin(1)
-                        .direction(Direction::Horizontal)
-                        .constraints(vec![Constraint::Percentage(20), Constraint::Percentage(80)])
-                        .split(main_board);
-                    let main_left_board = main_board[0];
-                    let main_mid_board = main_board[1];
-                    // let main_right_board = main_board[2];
-
                     //play list
-                    let main_left_block = Block::default().borders(Borders::RIGHT);
-                    let playlists_list_items: Vec<ListItem> = home_tab_state
-                        .playlists
-                        .iter()
-                        .map(|p| ListItem::new(Spans::from(vec![Span::raw(p.name.clone())])))
-                        .collect();
-                    let play_list = List::new(playlists_list_items)
-                        .block(main_left_block)
-                        .highlight_style(Style::default().fg(Color::Yellow));
-                    f.render_stateful_widget(
-                        play_list,
-                        main_left_board,
-                        &mut home_tab_state.playlists_state,
-                    );
-                    //songs list corresponding to the current play list
-                    let current_playlist_idx = home_tab_state.playlists_state.selected().unwrap();
-                    let main_mid_block = Block::default();
-                    let songs = &home_tab_state.playlists[current_playlist_idx].songs;
-                    let songs_list_state =
-                        &mut home_tab_state.songs_list_states[current_playlist_idx];
-                    if !songs.is_empty() {
-                        let song_list_items: Vec<ListItem> = songs
+                    if let Some(main_left_board) = main_left_board {
+                        let main_left_block = Block::default().borders(Borders::RIGHT);
+                        if filtering_scope == Some(SearchScope::Playlists) {
+                            let hits = app_state.search.as_ref().map_or(&[][..], |s| &s.hits);
+                            let playlists_list_items: Vec<ListItem> = hits
+                                .iter()
+                                .filter_map(|hit| match hit {
+                                    SearchHit::Playlist(i) => home_tab_state.playlists.get(*i),
+                                    _ => None,
+                                })
+                                .map(|p| {
+                                    ListItem::new(Spans::from(vec![Span::raw(p.name.clone())]))
+                                })
+                                .collect();
+                            let play_list = List::new(playlists_list_items)
+                                .block(main_left_block)
+                                .highlight_style(theme.highlight);
+                            f.render_stateful_widget(
+                                play_list,
+                                main_left_board,
+                                &mut filtered_list_state(),
+                            );
+                        } else {
+                            let playlists_list_items: Vec<ListItem> = home_tab_state
+                                .playlists
+                                .iter()
+                                .enumerate()
+                                .map(|(i, p)| {
+                                    ListItem::new(Spans::from(vec![Span::raw(p.name.clone())]))
+                                        .style(hit_style(SearchHit::Playlist(i)))
+                                })
+                                .collect();
+                            let play_list = List::new(playlists_list_items)
+                                .block(main_left_block)
+                                .highlight_style(theme.highlight);
+                            f.render_stateful_widget(
+                                play_list,
+                                main_left_board,
+                                &mut home_tab_state.playlists_state,
+                            );
+                            mouse_map.add_list(
+                                ListKind::Playlists,
+                                main_left_board,
+                                home_tab_state.playlists_state.selected(),
+                                home_tab_state.playlists.len(),
+                            );
+                        }
+                    }
+                    //songs table corresponding to the current play list
+                    if let Some(main_mid_board) = main_mid_board {
+                        let main_mid_block = Block::default();
+                        let marked_range = home_tab_state.marked_range();
+                        let current_playlist = &home_tab_state.playlists[current_playlist_idx];
+                        let songs = &current_playlist.songs;
+                        let songs_list_state =
+                            &mut home_tab_state.songs_list_states[current_playlist_idx];
+                        let song_columns = &home_tab_state.song_columns;
+                        let song_columns_widths: Vec<Constraint> =
+                            song_columns.iter().map(|c| c.width()).collect();
+                        let song_table_header = Row::new(song_columns.iter().map(|c| {
+                            let mut title = c.title().to_string();
+                            if let Some(sort) = current_playlist.sort.filter(|s| s.column == *c) {
+                                title.push_str(if sort.ascending { " ▲" } else { " ▼" });
+                            }
+                            Cell::from(title)
+                        }))
+                        .style(theme.heading)
+                        .bottom_margin(1);
+                        let song_stats = home_tab_state.song_stats.lock().unwrap();
+                        let broken_songs = &home_tab_state.broken_songs;
+                        let marked_songs = &home_tab_state.marked_songs;
+                        let song_row = |i: usize, s: &Song| {
+                            let stats = song_stats.get(&s.path);
+                            let mut style = hit_style(SearchHit::Song(current_playlist_idx, i));
+                            if marked_songs.contains(&s.path)
+                                || marked_range.as_ref().is_some_and(|r| r.contains(&i))
+                            {
+                                style = style.patch(theme.marked);
+                            }
+                            if broken_songs.contains(&s.path) {
+                                style = style.patch(theme.danger);
+                            }
+                            Row::new(
+                                song_columns
+                                    .iter()
+                                    .map(|c| Cell::from(c.cell_text(s, stats))),
+                            )
+                            .style(style)
+                        };
+                        if filtering_scope == Some(SearchScope::Songs(current_playlist_idx)) {
+                            let hits = app_state.search.as_ref().map_or(&[][..], |s| &s.hits);
+                            let song_rows: Vec<Row> = hits
+                                .iter()
+                                .filter_map(|hit| match hit {
+                                    SearchHit::Song(_, i) => Some((*i, songs.get(*i)?)),
+                                    _ => None,
+                                })
+                                .map(|(i, s)| song_row(i, s))
+                                .collect();
+                            let song_table = Table::new(song_rows)
+                                .header(song_table_header)
+                                .widths(&song_columns_widths)
+                                .block(main_mid_block)
+                                .highlight_style(theme.highlight);
+                            let mut filtered_table_state = TableState::default();
+                            filtered_table_state
+                                .select(app_state.search.as_ref().map(|s| s.current));
+                            f.render_stateful_widget(
+                                song_table,
+                                main_mid_board,
+                                &mut filtered_table_state,
+                            );
+                        } else if !songs.is_empty() {
+                            let song_rows: Vec<Row> = songs
+                                .iter()
+                                .enumerate()
+                                .map(|(i, s)| song_row(i, s))
+                                .collect();
+                            let song_table = Table::new(song_rows)
+                                .header(song_table_header)
+                                .widths(&song_columns_widths)
+                                .block(main_mid_block)
+                                .highlight_style(theme.highlight);
+                            f.render_stateful_widget(song_table, main_mid_board, songs_list_state);
+                            // the rows start below the header and its margin
+                            let song_rows_board = Rect::new(
+                                main_mid_board.x,
+                                main_mid_board.y + 2,
+                                main_mid_board.width,
+                                main_mid_board.height.saturating_sub(2),
+                            );
+                            mouse_map.add_list(
+                                ListKind::Songs(current_playlist_idx),
+                                song_rows_board,
+                                songs_list_state.selected(),
+                                songs.len(),
+                            );
+                        } else {
+                            f.render_widget(main_mid_block, main_mid_board);
+                        }
+                    }
+
+                    //recently played view over the playlists and songs
+                    if let HomeTabStateFocus::RecentlyPlayed = home_tab_state.focus {
+                        let recently_played_board =
+                            screen::overlay_rect(main_board, screen.compact);
+                        let recently_played_block = Block::default()
+                            .borders(Borders::ALL)
+                            .title(format!(
+                                "Recently Played ({}) Replay ({}) Queue ({}) Back",
+                                keymap.hint(Action::Play),
+                                keymap.hint(Action::Enqueue),
+                                keymap.hint(Action::Back)
+                            ))
+                            .style(theme.accent);
+                        let recently_played_items: Vec<ListItem> = home_tab_state
+                            .recently_played
                             .iter()
-                            .map(|s| ListItem::new(Spans::from(vec![Span::raw(s.name.clone())])))
+                            .map(|(entry, song)| {
+                                let title = match song {
+                                    Some(song) => song.display_title(),
+                                    None => format!("{} (missing)", entry.song_path),
+                                };
+                                ListItem::new(Spans::from(vec![
+                                    Span::styled(
+                                        format!(
+                                            "{:>10}  ",
+                                            stats::format_time_ago(entry.started_at)
+                                        ),
+                                        theme.muted,
+                                    ),
+                                    Span::raw(title),
+                                    Span::styled(
+                                        format!(
+                                            "  {} {}",
+                                            columns::format_duration(Duration::from_secs(
+                                                entry.listened_secs
+                                            )),
+                                            if entry.completed {
+                                                "✓"
+                                            } else if entry.stopped {
+                                                "stopped"
+                                            } else {
+                                                "skipped"
+                                            }
+                                        ),
+                                        theme.muted,
+                                    ),
+                                ]))
+                                .style(theme.text)
+                            })
                             .collect();
-                        let song_list = List::new(song_list_items)
-                            .block(main_mid_block)
-                            .highlight_style(Style::default().fg(Color::Yellow));
-                        f.render_stateful_widget(song_list, main_mid_board, songs_list_state);
-                    } else {
-                        f.render_widget(main_mid_block, main_mid_board);
+                        let recently_played_list = List::new(recently_played_items)
+                            .block(recently_played_block)
+                            .highlight_style(theme.highlight);
+                        f.render_widget(Clear, recently_played_board);
+                        f.render_stateful_widget(
+                            recently_played_list,
+                            recently_played_board,
+                            &mut home_tab_state.recently_played_state,
+                        );
+                        mouse_map.add(recently_played_board, MouseTarget::Cover);
+                        mouse_map.add_list(
+                            ListKind::RecentlyPlayed,
+                            recently_played_board.inner(&Margin {
+                                vertical: 1,
+                                horizontal: 1,
+                            }),
+                            home_tab_state.recently_played_state.selected(),
+                            home_tab_state.recently_played.len(),
+                        );
                     }
-                    //song info
-                    // let main_right_block = Block::default().borders(Borders::NONE);
-                    // f.render_widget(main_right_block, main_right_board);
-                }
-                //Source
-                1 => {
-                    let main_boards = Layout::default()
-                        .direction(Direction::Vertical)
-                        .constraints(
-                            [Constraint::Percentage(98), Constraint::Percentage(2)].as_ref(),
-                        )
-                        .split(main_board);
-                    let main_board = main_boards[0];
-                    let helper_board = main_boards[1];
 
+                    //duplicates view over the playlists and songs
+                    if let Some(view) = home_tab_state.duplicates_view.as_mut() {
+                        let duplicates_board = screen::overlay_rect(main_board, screen.compact);
+                        let duplicates_block = Block::default()
+                            .borders(Borders::ALL)
+                            .title(format!(
+                                "Duplicates: {} groups ({}) Keep this copy ({}) Back",
+                                view.groups.len(),
+                                keymap.hint(Action::Confirm),
+                                keymap.hint(Action::Back)
+                            ))
+                            .style(theme.accent);
+                        let duplicates_items: Vec<ListItem> = view
+                            .rows
+                            .iter()
+                            .map(|(group_idx, song_idx)| match song_idx {
+                                Some(song_idx) => {
+                                    let song = &view.groups[*group_idx].songs[*song_idx];
+                                    ListItem::new(format!(
+                                        "  {}  {}  {}",
+                                        song.path.to_string_lossy(),
+                                        columns::format_size(song.size),
+                                        song.duration
+                                            .map(columns::format_duration)
+                                            .unwrap_or_default()
+                                    ))
+                                    .style(theme.text)
+                                }
+                                None => {
+                                    let group = &view.groups[*group_idx];
+                                    ListItem::new(format!(
+                                        "{} ({})",
+                                        group.reason.describe(),
+                                        group.songs.len()
+                                    ))
+                                    .style(theme.heading)
+                                }
+                            })
+                            .collect();
+                        let duplicates_list = List::new(duplicates_items)
+                            .block(duplicates_block)
+                            .highlight_style(theme.highlight);
+                        f.render_widget(Clear, duplicates_board);
+                        f.render_stateful_widget(
+                            duplicates_list,
+                            duplicates_board,
+                            &mut view.state,
+                        );
+                        mouse_map.add(duplicates_board, MouseTarget::Cover);
+                        mouse_map.add_list(
+                            ListKind::Duplicates,
+                            duplicates_board.inner(&Margin {
+                                vertical: 1,
+                                horizontal: 1,
+                            }),
+                            view.state.selected(),
+                            view.rows.len(),
+                        );
+                    }
+                }
+                SOURCES_TAB => {
                     //main board
                     let main_block = Block::default().borders(Borders::ALL).title("Sources");
-                    let sources = source_tab_state.cloned_sources();
-                    let list_items: Vec<ListItem> = sources
+                    let sources = source_tab_state.source_db.sources.clone();
+                    let scan_recursive = source_tab_state.scan_recursive;
+                    // the state, name and what the last scan found, then the options
+                    let source_item = |source: &Source, name_style: Style| {
+                        let (mark, name_style) = if source.enabled {
+                            ("[x] ", theme.text.patch(name_style))
+                        } else {
+                            ("[ ] ", theme.muted.patch(name_style))
+                        };
+                        let mut spans = vec![
+                            Span::styled(mark, theme.muted),
+                            Span::styled(source.display_name().to_string(), name_style),
+                        ];
+                        if source.label.is_some() {
+                            spans.push(Span::styled(format!("  {}", source.path), theme.muted));
+                        }
+                        if !source.is_reachable() {
+                            spans.push(Span::styled("  unreachable", theme.error));
+                        }
+                        let mut details = match source.last_scan {
+                            Some(last_scan) => format!(
+                                "  {} tracks, {}, scanned {}",
+                                source.tracks,
+                                columns::format_size(source.size),
+                                stats::format_time_ago(last_scan)
+                            ),
+                            None => "  not scanned".to_string(),
+                        };
+                        if source.is_recursive(scan_recursive) {
+                            details.push_str(", recursive");
+                        }
+                        if !source.excludes.is_empty() {
+                            details.push_str(&format!(", excludes {}", source.excludes.join(" ")));
+                        }
+                        spans.push(Span::styled(details, theme.muted));
+                        ListItem::new(Spans::from(spans))
+                    };
+                    if filtering_scope == Some(SearchScope::Sources) {
+                        let hits = app_state.search.as_ref().map_or(&[][..], |s| &s.hits);
+                        let list_items: Vec<ListItem> = hits
+                            .iter()
+                            .filter_map(|hit| match hit {
+                                SearchHit::Source(i) => sources.get(*i),
+                                _ => None,
+                            })
+                            .map(|s| source_item(s, Style::default()))
+                            .collect();
+                        let main_content = List::new(list_items)
+                            .block(main_block)
+                            .highlight_style(theme.highlight);
+                        f.render_stateful_widget(
+                            main_content,
+                            main_board,
+                            &mut filtered_list_state(),
+                        );
+                    } else {
+                        let list_items: Vec<ListItem> = sources
+                            .iter()
+                            .enumerate()
+                            .map(|(i, s)| source_item(s, hit_style(SearchHit::Source(i))))
+                            .collect();
+                        let main_content = List::new(list_items)
+                            .block(main_block)
+                            .highlight_style(theme.highlight);
+                        f.render_stateful_widget(
+                            main_content,
+                            main_board,
+                            &mut source_tab_state.sources_list_state,
+                        );
+                        mouse_map.add_list(
+                            ListKind::Sources,
+                            main_board.inner(&Margin {
+                                vertical: 1,
+                                horizontal: 1,
+                            }),
+                            source_tab_state.sources_list_state.selected(),
+                            sources.len(),
+                        );
+                    }
+
+                    //folder browser over the sources list
+                    if let Some(browser) = source_tab_state.browser.as_mut() {
+                        let browser_board = screen::overlay_rect(main_board, screen.compact);
+                        let browser_list_board = browser_board.inner(&Margin {
+                            vertical: 1,
+                            horizontal: 1,
+                        });
+                        let browser_block = Block::default()
+                            .borders(Borders::ALL)
+                            .title(format!(
+                                "{} ({}) Open ({}) Parent ({}) Add as source",
+                                browser.folder.to_string_lossy(),
+                                keymap.hint(Action::Confirm),
+                                keymap.hint(Action::Back),
+                                keymap.hint(Action::AddSource)
+                            ))
+                            .style(theme.accent);
+                        // folders are counted once they have been on screen
+                        let counted = browser.state.selected().unwrap_or(0)
+                            + browser_list_board.height as usize;
+                        let audio_counts: Vec<Option<(usize, bool)>> = (0..browser.entries.len())
+                            .map(|i| {
+                                let entry = &browser.entries[i];
+                                if !entry.is_folder || i >= counted {
+                                    return None;
+                                }
+                                let path = entry.path.clone();
+                                Some(browser.audio_count(&path))
+                            })
+                            .collect();
+                        let browser_items: Vec<ListItem> = match browser.error.as_ref() {
+                            Some(error) => {
+                                vec![ListItem::new(format!("Cannot read: {}", error))
+                                    .style(theme.error)]
+                            }
+                            None if browser.entries.is_empty() => {
+                                vec![ListItem::new("No folders or audio files").style(theme.muted)]
+                            }
+                            None => browser
+                                .entries
+                                .iter()
+                                .zip(audio_counts)
+                                .map(|(entry, audio_count)| match audio_count {
+                                    Some((count, complete)) => ListItem::new(Spans::from(vec![
+                                        Span::styled(format!("{}/", entry.name), theme.text),
+                                        Span::styled(
+                                            format!(
+                                                "  {}{} audio files",
+                                                count,
+                                                if complete { "" } else { "+" }
+                                            ),
+                                            theme.muted,
+                                        ),
+                                    ])),
+                                    None if entry.is_folder => {
+                                        ListItem::new(format!("{}/", entry.name)).style(theme.text)
+                                    }
+                                    None => ListItem::new(entry.name.clone()).style(theme.muted),
+                                })
+                                .collect(),
+                        };
+                        let browser_list = List::new(browser_items)
+                            .block(browser_block)
+                            .highlight_style(theme.highlight);
+                        f.render_widget(Clear, browser_board);
+                        f.render_stateful_widget(browser_list, browser_board, &mut browser.state);
+                        mouse_map.add(browser_board, MouseTarget::Cover);
+                        mouse_map.add_list(
+                            ListKind::Browser,
+                            browser_list_board,
+                            browser.state.selected(),
+                            browser.entries.len(),
+                        );
+                    }
+                }
+                STATS_TAB => {
+                    let main_block = Block::default().borders(Borders::ALL).title(format!(
+                        "Stats: {} ({}) Change range",
+                        stats_tab_state.range.title(),
+                        keymap.hint(Action::CycleStatsRange)
+                    ));
+                    f.render_widget(main_block, main_board);
+                    if let Some(report) = stats_tab_state.report.as_ref() {
+                        let (summary_board, top_board, chart_board) = screen::split_stats(
+                            main_board.inner(&Margin {
+                                vertical: 1,
+                                horizontal: 1,
+                            }),
+                            screen.compact,
+                        );
+
+                        //summary
+                        let listened_secs = report.total_listened.as_secs();
+                        let summary = Paragraph::new(Spans::from(vec![
+                            Span::styled("Listening time: ", theme.accent),
+                            Span::raw(format!(
+                                "{}h {:02}m",
+                                listened_secs / 3600,
+                                listened_secs % 3600 / 60
+                            )),
+                            Span::raw(" ".repeat(5)),
+                            Span::styled("Plays: ", theme.accent),
+                            Span::raw(report.plays.to_string()),
+                            Span::raw(" ".repeat(5)),
+                            Span::styled("Skips: ", theme.accent),
+                            Span::raw(report.skips.to_string()),
+                        ]))
+                        .alignment(Alignment::Center);
+                        f.render_widget(summary, summary_board);
+
+                        //top artists, albums and tracks
+                        let counts_list = |title: &'static str, counts: &[(String, usize)]| {
+                            let items: Vec<ListItem> = counts
+                                .iter()
+                                .enumerate()
+                                .map(|(i, (name, count))| {
+                                    ListItem::new(format!("{:>2}. {} ({})", i + 1, name, count))
+                                })
+                                .collect();
+                            List::new(items)
+                                .block(Block::default().borders(Borders::ALL).title(title))
+                        };
+                        let top_boards = Layout::default()
+                            .direction(Direction::Horizontal)
+                            .constraints(
+                                [
+                                    Constraint::Percentage(30),
+                                    Constraint::Percentage(30),
+                                    Constraint::Percentage(40),
+                                ]
+                                .as_ref(),
+                            )
+                            .split(top_board);
+                        f.render_widget(
+                            counts_list("Top Artists", &report.top_artists),
+                            top_boards[0],
+                        );
+                        f.render_widget(
+                            counts_list("Top Albums", &report.top_albums),
+                            top_boards[1],
+                        );
+                        f.render_widget(
+                            counts_list("Top Tracks", &report.top_tracks),
+                            top_boards[2],
+                        );
+
+                        //listening by hour and most skipped tracks
+                        if let Some(chart_board) = chart_board {
+                            let bottom_boards = Layout::default()
+                                .direction(Direction::Horizontal)
+                                .constraints(
+                                    [Constraint::Percentage(60), Constraint::Percentage(40)]
+                                        .as_ref(),
+                                )
+                                .split(chart_board);
+                            let hour_labels: Vec<String> = (0..24).map(|h| h.to_string()).collect();
+                            let hour_data: Vec<(&str, u64)> = hour_labels
+                                .iter()
+                                .map(|h| h.as_str())
+                                .zip(report.minutes_by_hour.iter().copied())
+                                .collect();
+                            let bar_width = (bottom_boards[0].width.saturating_sub(2) / 24)
+                                .saturating_sub(1)
+                                .max(1);
+                            let hours_chart = BarChart::default()
+                                .block(
+                                    Block::default()
+                                        .borders(Borders::ALL)
+                                        .title("Minutes listened by hour"),
+                                )
+                                .data(&hour_data)
+                                .bar_width(bar_width)
+                                .bar_gap(1)
+                                .bar_style(theme.chart)
+                                .value_style(theme.chart_value);
+                            f.render_widget(hours_chart, bottom_boards[0]);
+                            f.render_widget(
+                                counts_list("Most Skipped", &report.most_skipped),
+                                bottom_boards[1],
+                            );
+                        }
+                    }
+                }
+                SETTINGS_TAB => {
+                    let main_block = Block::default().borders(Borders::ALL).title(format!(
+                        "Settings ({}) Change",
+                        keymap.hint(Action::Confirm)
+                    ));
+                    let settings = &settings_state.settings;
+                    let list_items: Vec<ListItem> = SettingItem::ALL
                         .iter()
-                        .map(|s| ListItem::new(s.clone()).style(Style::default().fg(Color::White)))
+                        .map(|item| {
+                            ListItem::new(Spans::from(vec![
+                                Span::styled(format!("{:<42}", item.title()), theme.text),
+                                Span::styled(item.value_text(settings), theme.value),
+                            ]))
+                        })
                         .collect();
                     let main_content = List::new(list_items)
                         .block(main_block)
-                        .highlight_style(Style::default().fg(Color::Yellow));
+                        .highlight_style(theme.highlight);
                     f.render_stateful_widget(
                         main_content,
                         main_board,
-                        &mut source_tab_state.sources_list_state,
+                        &mut settings_state.settings_list_state,
+                    );
+                    mouse_map.add_list(
+                        ListKind::Settings,
+                        main_board.inner(&Margin {
+                            vertical: 1,
+                            horizontal: 1,
+                        }),
+                        settings_state.settings_list_state.selected(),
+                        SettingItem::ALL.len(),
                     );
+                }
+                _ => {}
+            }
+
+            // edit popup
+            if let InputMode::Edit = app_state.input_mode {
+                let pop_up_board =
+                    screen::centered_rect(main_board, (main_board.width * 2 / 3).max(40), 7);
+                let pop_up_block = Block::default().borders(Borders::ALL).style(theme.accent);
+                f.render_widget(Clear, pop_up_board);
+                f.render_widget(pop_up_block, pop_up_board);
+
+                // title line, then the input below a blank line
+                let pop_up_board = pop_up_board.inner(&Margin {
+                    vertical: 1,
+                    horizontal: 1,
+                });
+                let pop_up_title_board =
+                    Rect::new(pop_up_board.x, pop_up_board.y, pop_up_board.width, 1);
+                let mut pop_up_content_board = Rect::new(
+                    pop_up_board.x + 1,
+                    pop_up_board.y + 2,
+                    pop_up_board.width.saturating_sub(2).max(1),
+                    pop_up_board.height.saturating_sub(2).max(1),
+                );
 
-                    //helper board
-                    let text = Spans::from(vec![
-                        Span::styled("(a) Add new source", Style::default().fg(Color::Magenta)),
-                        Span::raw(" ".repeat(10)),
-                        Span::styled("(d) Delete source", Style::default().fg(Color::Red)),
-                    ]);
-                    let helper_content = Paragraph::new(text)
-                        .alignment(Alignment::Center)
-                        .wrap(Wrap { trim: true });
-                    f.render_widget(helper_content, helper_board);
-
-                    //pop up board
-                    if let InputMode::Edit = app_state.input_mode {
-                        let pop_up_board = Rect::new(
-                            main_board.width / 2 - main_board.width / 3,
-                            main_board.height / 2,
-                            main_board.width * 2 / 3,
-                            main_board.height / 4,
+                // the completion candidates, or what the tab found out about the input,
+                // on the last line
+                let status_line = match app_state.completion.as_ref() {
+                    Some((base, candidates, _)) => {
+                        let names: Vec<&str> = candidates
+                            .iter()
+                            .map(|c| c.strip_prefix(base.as_str()).unwrap_or(c))
+                            .map(|c| c.rsplit('/').find(|s| !s.is_empty()).unwrap_or(c))
+                            .collect();
+                        Some(Span::styled(names.join("  "), theme.muted))
+                    }
+                    None => input_status.as_ref().map(|(text, good)| {
+                        Span::styled(
+                            text.clone(),
+                            if *good { theme.success } else { theme.error },
+                        )
+                    }),
+                };
+                if let Some(status_line) = status_line {
+                    if pop_up_content_board.height >= 2 {
+                        pop_up_content_board.height -= 1;
+                        let status_board = Rect::new(
+                            pop_up_content_board.x,
+                            pop_up_content_board.bottom(),
+                            pop_up_content_board.width,
+                            1,
                         );
-                        let pop_up_block = Block::default()
-                            .borders(Borders::ALL)
-                            .style(Style::default().fg(Color::Yellow));
-                        f.render_widget(pop_up_block, pop_up_board);
+                        f.render_widget(Paragraph::new(Spans::from(status_line)), status_board);
+                    }
+                }
 
-                        let pop_up_board = Layout::default()
-                            .constraints(
-                                [Constraint::Percentage(10), Constraint::Percentage(95)].as_ref(),
-                            )
-                            .split(pop_up_board);
-                        let pop_up_title_board = pop_up_board[0];
-                        let pop_up_content_board = Layout::default()
-                            .constraints([Constraint::Percentage(100)])
-                            .margin(2)
-                            .split(pop_up_board[1])[0];
-
-                        let pop_up_title = Paragraph::new(Spans::from(vec![Span::styled(
-                            "Absolute Path:",
-                            Style::default().fg(Color::Yellow),
-                        )]))
-                        .alignment(Alignment::Center);
-                        f.render_widget(pop_up_title, pop_up_title_board);
+                let pop_up_title = Paragraph::new(Spans::from(vec![Span::styled(
+                    app_state.edit_prompt,
+                    theme.accent,
+                )]))
+                .alignment(Alignment::Center);
+                f.render_widget(pop_up_title, pop_up_title_board);
 
-                        let pop_up_input = Paragraph::new(Spans::from(vec![Span::styled(
-                            app_state
-                                .input_stream
-                                .iter()
-                                .map(|s| s.to_string())
-                                .collect::<String>(),
-                            Style::default().fg(Color::White),
-                        )]))
-                        .wrap(Wrap { trim: true });
-                        let input_stream_len = app_state.input_stream.len() as u16;
-                        let scroll_offset_y = if input_stream_len / pop_up_content_board.width > 3 {
-                            input_stream_len / pop_up_content_board.width - 3
+                // wrapped by display width, scrolled to keep the cursor line in view
+                let (input_lines, (cursor_y, cursor_x)) =
+                    app_state.input.wrapped(pop_up_content_board.width);
+                let scroll_offset_y =
+                    cursor_y.saturating_sub(pop_up_content_board.height.saturating_sub(1));
+                let input_lines: Vec<Spans> = input_lines
+                    .into_iter()
+                    .skip(scroll_offset_y as usize)
+                    .map(|line| Spans::from(Span::styled(line, theme.text)))
+                    .collect();
+                f.render_widget(Paragraph::new(input_lines), pop_up_content_board);
+                f.set_cursor(
+                    pop_up_content_board.left() + cursor_x,
+                    pop_up_content_board.top() + cursor_y - scroll_offset_y,
+                );
+            }
+
+            // the keys of the focused view, the help key last
+            if let Some(hints_board) = screen.hints {
+                let mut hints: Vec<Span> = vec![];
+                for (action, label) in tab_actions.iter().chain([&(Action::Help, "Help")]) {
+                    if keymap.keys_of(*action).is_empty() {
+                        continue;
+                    }
+                    hints.push(Span::styled(
+                        format!("({}) {}", keymap.hint(*action), label),
+                        theme.muted,
+                    ));
+                    hints.push(Span::raw("   "));
+                }
+                f.render_widget(Paragraph::new(Spans::from(hints)), hints_board);
+            }
+
+            // help overlay, every action of the focused view and the global ones with their keys
+            if let InputMode::Help = app_state.input_mode {
+                let mut lines: Vec<Spans> = vec![];
+                for (title, actions) in [
+                    (tab_title.as_str(), &tab_actions),
+                    ("Global", &global_actions),
+                ] {
+                    if actions.is_empty() {
+                        continue;
+                    }
+                    if !lines.is_empty() {
+                        lines.push(Spans::default());
+                    }
+                    lines.push(Spans::from(Span::styled(title, theme.heading)));
+                    for (action, label) in actions.iter() {
+                        let keys = keymap.keys_of(*action);
+                        let (keys, keys_style) = if keys.is_empty() {
+                            ("unbound".to_string(), theme.muted)
                         } else {
-                            0
+                            (keys.join(", "), theme.value)
                         };
-                        let pop_up_input = pop_up_input.scroll((scroll_offset_y, 0));
-                        f.set_cursor(
-                            pop_up_content_board.left()
-                                + input_stream_len % pop_up_content_board.width,
-                            pop_up_content_board.top()
-                                + input_stream_len / pop_up_content_board.width
-                                - scroll_offset_y,
-                        );
-                        f.render_widget(pop_up_input, pop_up_content_board);
+                        lines.push(Spans::from(vec![
+                            Span::styled(format!("  {:<14}", keys), keys_style),
+                            Span::styled(*label, theme.text),
+                        ]));
                     }
                 }
-                //Settings
-                2 => {
-                    let main_block = Block::default().borders(Borders::ALL).title("Settings");
-                    f.render_widget(main_block, main_board);
+                let help_board = screen::centered_rect(main_board, 56, lines.len() as u16 + 2);
+                // no scrolling past the last line
+                let max_scroll =
+                    (lines.len() as u16).saturating_sub(help_board.height.saturating_sub(2));
+                app_state.overlay_scroll = app_state.overlay_scroll.min(max_scroll);
+                let help_content = Paragraph::new(lines)
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title("Keys (j/k) Scroll (any other key) Close")
+                            .style(theme.accent),
+                    )
+                    .scroll((app_state.overlay_scroll, 0));
+                f.render_widget(Clear, help_board);
+                f.render_widget(help_content, help_board);
+            }
+
+            // message log overlay, newest first
+            if let InputMode::MessageLog = app_state.input_mode {
+                let mut lines: Vec<Spans> = app_state
+                    .notifications
+                    .log()
+                    .map(|notification| {
+                        let style = match notification.level {
+                            Level::Error => theme.error,
+                            Level::Info => theme.text,
+                        };
+                        Spans::from(vec![
+                            Span::styled(
+                                format!("{:>10}  ", stats::format_time_ago(notification.at)),
+                                theme.muted,
+                            ),
+                            Span::styled(notification.text.clone(), style),
+                        ])
+                    })
+                    .collect();
+                if lines.is_empty() {
+                    lines.push(Spans::from(Span::styled("No messages yet", theme.muted)));
+                }
+                let log_board = screen::overlay_rect(main_board, screen.compact);
+                let max_scroll =
+                    (lines.len() as u16).saturating_sub(log_board.height.saturating_sub(2));
+                app_state.overlay_scroll = app_state.overlay_scroll.min(max_scroll);
+                let log_content = Paragraph::new(lines)
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title(format!(
+                                "Messages ({}) (j/k) Scroll (any other key) Close",
+                                app_state.notifications.len()
+                            ))
+                            .style(theme.accent),
+                    )
+                    .scroll((app_state.overlay_scroll, 0));
+                f.render_widget(Clear, log_board);
+                f.render_widget(log_content, log_board);
+            }
+
+            // quit confirmation
+            if let InputMode::ConfirmQuit = app_state.input_mode {
+                let confirm_board = screen::centered_rect(main_board, 30, 3);
+                let confirm_content = Paragraph::new("Quit songbreeze? (y/n)")
+                    .alignment(Alignment::Center)
+                    .block(Block::default().borders(Borders::ALL).style(theme.accent));
+                f.render_widget(Clear, confirm_board);
+                f.render_widget(confirm_content, confirm_board);
+            }
+
+            // source removal confirmation
+            if let (InputMode::ConfirmSourceRemoval, Some(removal)) =
+                (&app_state.input_mode, source_tab_state.removal.as_ref())
+            {
+                let mut lines = vec![
+                    Spans::from(Span::styled(
+                        format!("Remove source {}?", removal.name),
+                        theme.accent,
+                    )),
+                    Spans::from(Span::styled(
+                        format!(
+                            "{} tracks leave the library, no other source has them",
+                            removal.library_tracks
+                        ),
+                        theme.text,
+                    )),
+                ];
+                if removal.playlist_entries > 0 {
+                    lines.push(Spans::from(Span::styled(
+                        format!(
+                            "{} of them are in {} playlists",
+                            removal.playlist_entries, removal.playlists
+                        ),
+                        theme.text,
+                    )));
+                    lines.push(Spans::from(Span::styled(
+                        "(p) Prune them (k) Keep them (n) Cancel",
+                        theme.accent,
+                    )));
+                } else {
+                    lines.push(Spans::from(Span::styled("(y/n)", theme.accent)));
+                }
+                let confirm_board = screen::centered_rect(main_board, 60, lines.len() as u16 + 2);
+                let confirm_content = Paragraph::new(lines)
+                    .alignment(Alignment::Center)
+                    .wrap(Wrap { trim: true })
+                    .block(Block::default().borders(Borders::ALL).style(theme.accent));
+                f.render_widget(Clear, confirm_board);
+                f.render_widget(confirm_content, confirm_board);
+            }
+
+            // search bar at the bottom of the main board
+            if let (InputMode::Search, Some(search)) = (&app_state.input_mode, &app_state.search) {
+                if main_board.height > 2 {
+                    let search_board = Rect::new(
+                        main_board.x + 1,
+                        main_board.bottom() - 2,
+                        main_board.width.saturating_sub(2),
+                        1,
+                    );
+                    let prefix = match search.scope {
+                        SearchScope::Library => "Search library: ",
+                        _ => "/",
+                    };
+                    let count = if app_state.input.is_empty() {
+                        String::new()
+                    } else if search.hits.is_empty() {
+                        "  [no match]".to_string()
+                    } else {
+                        format!("  [{}/{}]", search.current + 1, search.hits.len())
+                    };
+                    let prefix_width = prefix.len() as u16;
+                    let (query, cursor_x) = app_state
+                        .input
+                        .view(search_board.width.saturating_sub(prefix_width + 1));
+                    let search_content = Paragraph::new(Spans::from(vec![
+                        Span::styled(prefix, theme.accent),
+                        Span::styled(query, theme.text),
+                        Span::styled(count, theme.muted),
+                    ]));
+                    f.render_widget(Clear, search_board);
+                    f.render_widget(search_content, search_board);
+                    f.set_cursor(search_board.x + prefix_width + cursor_x, search_board.y);
+                }
+            }
+
+            // command line or the message of the last command at the bottom of the main board
+            let bottom_line = match (&app_state.input_mode, app_state.notifications.current()) {
+                (InputMode::Command, _) => {
+                    let (line, cursor_x) = app_state
+                        .input
+                        .view(main_board.width.saturating_sub(3).saturating_sub(1));
+                    let candidates = match app_state.completion.as_ref() {
+                        Some((_, candidates, _)) if candidates.len() > 1 => {
+                            format!("  [{}]", candidates.join(" "))
+                        }
+                        _ => String::new(),
+                    };
+                    Some((
+                        Spans::from(vec![
+                            Span::styled(":", theme.accent),
+                            Span::styled(line, theme.text),
+                            Span::styled(candidates, theme.muted),
+                        ]),
+                        Some(1 + cursor_x),
+                    ))
+                }
+                (InputMode::Normal, Some(notification)) => {
+                    let text = match notification.level {
+                        Level::Error => Spans::from(vec![
+                            Span::styled(notification.text.clone(), theme.error),
+                            Span::styled(
+                                format!("  ({}) Dismiss", keymap.hint(Action::DismissMessage)),
+                                theme.muted,
+                            ),
+                        ]),
+                        Level::Info => {
+                            Spans::from(Span::styled(notification.text.clone(), theme.success))
+                        }
+                    };
+                    Some((text, None))
+                }
+                _ => None,
+            };
+            if let Some((text, cursor_x)) = bottom_line {
+                if main_board.height > 2 {
+                    let bottom_board = Rect::new(
+                        main_board.x + 1,
+                        main_board.bottom() - 2,
+                        main_board.width.saturating_sub(2),
+                        1,
+                    );
+                    f.render_widget(Clear, bottom_board);
+                    f.render_widget(Paragraph::new(text), bottom_board);
+                    if let Some(cursor_x) = cursor_x {
+                        f.set_cursor(bottom_board.x + cursor_x, bottom_board.y);
+                    }
                 }
-                _ => {}
             }
 
             // === draw player board ===
+            let player_board = match screen.player {
+                Some(player_board) => player_board,
+                None => return,
+            };
+            let player_content = Paragraph::new(Spans::from(vec![
+                Span::styled(format!("({}) Play", keymap.hint(Action::Play)), theme.text),
+                Span::raw(" ".repeat(5)),
+                Span::styled(
+                    format!("({}) Next", keymap.hint(Action::NextSong)),
+                    theme.text,
+                ),
+                Span::raw(" ".repeat(5)),
+                Span::styled(format!("({}) Stop", keymap.hint(Action::Stop)), theme.text),
+                Span::raw(" ".repeat(5)),
+                Span::styled(
+                    format!(
+                        "({}) {}",
+                        keymap.hint(Action::CyclePlayMode),
+                        app_state.playback.play_mode().title()
+                    ),
+                    theme.text,
+                ),
+                Span::raw(" ".repeat(5)),
+                Span::styled(format!("Vol {}%", app_state.playback.volume), theme.text),
+                Span::raw(" ".repeat(5)),
+                Span::styled(
+                    format!("({}) Mini player", keymap.hint(Action::ToggleMiniPlayer)),
+                    theme.text,
+                ),
+            ]));
+            // the compact player bar is a single status line
+            if screen.compact {
+                let player_content = Paragraph::new(Spans::from(vec![
+                    Span::styled(now_playing_title, theme.text),
+                    Span::styled(
+                        format!("  {}  {}", progress_label, player_status),
+                        theme.muted,
+                    ),
+                ]));
+                f.render_widget(player_content, player_board);
+                return;
+            }
             // draw player block
             let player_block = Block::default().borders(Borders::ALL);
             f.render_widget(player_block, player_board);
@@ -1033,55 +5038,21 @@ fn main() -> Result<(), io::Error> {
                 .direction(Direction::Vertical)
                 .vertical_margin(1)
                 .horizontal_margin(3)
-                .constraints(vec![Constraint::Percentage(50), Constraint::Percentage(50)])
+                .constraints(vec![Constraint::Length(1), Constraint::Length(1)])
                 .split(player_board);
             let player_progress_board = player_board[0];
             let player_content_board = player_board[1];
             //draw progress
+            let progress_label = format!("{}  {}", now_playing_title, progress_label);
+            mouse_map.add_progress(player_progress_board, &progress_label);
             let player_progress = LineGauge::default()
-                .gauge_style(
-                    Style::default()
-                        .fg(Color::Blue)
-                        .bg(Color::Black)
-                        .add_modifier(Modifier::BOLD),
-                )
+                .gauge_style(theme.gauge)
                 .line_set(symbols::line::THICK)
-                .ratio(0.4);
+                .label(progress_label)
+                .ratio(progress_ratio.clamp(0.0, 1.0));
             f.render_widget(player_progress, player_progress_board);
-            let player_content = Paragraph::new(Spans::from(vec![
-                Span::styled("(p) Play", Style::default().fg(Color::White)),
-                Span::raw(" ".repeat(5)),
-                Span::styled("(<) Previous", Style::default().fg(Color::White)),
-                Span::raw(" ".repeat(5)),
-                Span::styled("(>) Next", Style::default().fg(Color::White)),
-            ]));
             f.render_widget(player_content, player_content_board);
         })?;
     }
-    disable_raw_mode()?;
     Ok(())
 }
-
-#[cfg(test)]
-mod tests {
-    use rodio::{queue::SourcesQueueOutput, source::SineWave, Sink, Source};
-
-    use super::*;
-
-    struct Mock {
-        sink: Sink,
-        stream_handle: rodio::OutputStreamHandle,
-        output_stream: rodio::OutputStream,
-    }
-    impl Mock {
-        fn new() -> Self {
-            let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-            let sink = Sink::try_new(&stream_handle).unwrap();
-            Self {
-                sink,
-                stream_handle,
-                output_stream: _stream,
-            }
-        }
-    }
-}
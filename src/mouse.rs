@@ -0,0 +1,127 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tui::layout::Rect;
+
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+// lists that can be clicked and scrolled
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ListKind {
+    Playlists,
+    Songs(usize), // of the playlist at this index
+    Sources,
+    Browser, // the folder browser of the sources tab
+    Settings,
+    RecentlyPlayed,
+    Duplicates,
+}
+
+// what is under the mouse
+#[derive(Clone, Copy, PartialEq)]
+pub enum MouseTarget {
+    Tab(usize),
+    Row(ListKind, usize), // item index in the list
+    Progress(f64),        // ratio of the progress gauge
+    Cover,                // an overlay, hides what is drawn under it
+}
+
+// what was clickable in the last frame, the draw code fills it and the mouse events read it
+pub struct MouseMap {
+    areas: Vec<(Rect, MouseTarget)>,
+    // tui keeps the scroll offsets of lists private, they are mirrored here
+    list_offsets: HashMap<ListKind, usize>,
+    last_click: Option<(Instant, u16, u16)>,
+}
+
+impl MouseMap {
+    pub fn new() -> Self {
+        Self {
+            areas: vec![],
+            list_offsets: HashMap::new(),
+            last_click: None,
+        }
+    }
+}
+
+impl MouseMap {
+    pub fn clear(&mut self) {
+        self.areas.clear();
+    }
+    pub fn add(&mut self, area: Rect, target: MouseTarget) {
+        self.areas.push((area, target));
+    }
+    // titles of a `Tabs` widget drawn on `area`, each is padded by a space and followed by a divider
+    pub fn add_tabs(&mut self, area: Rect, titles: &[String]) {
+        let mut x = area.x;
+        for (i, title) in titles.iter().enumerate() {
+            x += 1;
+            let width = (title.chars().count() as u16).min(area.right().saturating_sub(x));
+            if width == 0 {
+                break;
+            }
+            self.add(Rect::new(x, area.y, width, 1), MouseTarget::Tab(i));
+            x += width + 2;
+        }
+    }
+    // a list of one line items drawn on `area`, scrolled the way tui scrolls it
+    pub fn add_list(&mut self, kind: ListKind, area: Rect, selected: Option<usize>, len: usize) {
+        if len == 0 || area.height == 0 {
+            return;
+        }
+        let height = area.height as usize;
+        let offset = self
+            .list_offsets
+            .get(&kind)
+            .copied()
+            .unwrap_or(0)
+            .min(len - 1);
+        let selected = selected.unwrap_or(0).min(len - 1);
+        let offset = if selected >= offset + height {
+            selected + 1 - height
+        } else {
+            offset.min(selected)
+        };
+        self.list_offsets.insert(kind, offset);
+        for (row, item_idx) in (offset..len).take(height).enumerate() {
+            let row_area = Rect::new(area.x, area.y + row as u16, area.width, 1);
+            self.add(row_area, MouseTarget::Row(kind, item_idx));
+        }
+    }
+    // a `LineGauge` drawn on `area`, its line starts a space after the label
+    pub fn add_progress(&mut self, area: Rect, label: &str) {
+        let line_start = (label.chars().count() as u16 + 1).min(area.width);
+        let line_area = Rect::new(area.x + line_start, area.y, area.width - line_start, 1);
+        self.add(line_area, MouseTarget::Progress(0.0));
+    }
+    // the last drawn area under the cell wins
+    pub fn target_at(&self, column: u16, row: u16) -> Option<MouseTarget> {
+        let (area, target) = self.areas.iter().rev().find(|(area, _)| {
+            (area.left()..area.right()).contains(&column)
+                && (area.top()..area.bottom()).contains(&row)
+        })?;
+        match target {
+            MouseTarget::Progress(_) => Some(MouseTarget::Progress(
+                (column - area.x) as f64 / area.width.max(1) as f64,
+            )),
+            MouseTarget::Cover => None,
+            target => Some(*target),
+        }
+    }
+    // true if this click is the second one on the same cell
+    pub fn is_double_click(&mut self, column: u16, row: u16) -> bool {
+        let now = Instant::now();
+        let double = self.last_click.is_some_and(|(at, c, r)| {
+            c == column && r == row && now.duration_since(at) < DOUBLE_CLICK_INTERVAL
+        });
+        // a third click starts over
+        self.last_click = if double {
+            None
+        } else {
+            Some((now, column, row))
+        };
+        double
+    }
+}
@@ -0,0 +1,85 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::stats;
+
+const INFO_SHOWN_FOR: Duration = Duration::from_secs(4);
+const LOG_CAPACITY: usize = 200;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Error,
+}
+
+pub struct Notification {
+    pub text: String,
+    pub level: Level,
+    pub at: u64, // unix timestamp in seconds
+}
+
+// the status line shows the last message, the message log keeps the older ones
+pub struct Notifications {
+    log: VecDeque<Notification>, // oldest first
+    shown_at: Option<Instant>,   // when the last message went up, None once dismissed
+}
+
+impl Notifications {
+    pub fn new() -> Self {
+        Self {
+            log: VecDeque::new(),
+            shown_at: None,
+        }
+    }
+}
+
+impl Notifications {
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.push(Level::Info, text.into());
+    }
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.push(Level::Error, text.into());
+    }
+    fn push(&mut self, level: Level, text: String) {
+        if self.log.len() == LOG_CAPACITY {
+            self.log.pop_front();
+        }
+        self.log.push_back(Notification {
+            text,
+            level,
+            at: stats::now(),
+        });
+        self.shown_at = Some(Instant::now());
+    }
+    // infos go away after a few seconds, errors stay until dismissed or replaced
+    pub fn current(&self) -> Option<&Notification> {
+        let shown_at = self.shown_at?;
+        let last = self.log.back()?;
+        match last.level {
+            Level::Info if shown_at.elapsed() > INFO_SHOWN_FOR => None,
+            _ => Some(last),
+        }
+    }
+    pub fn dismiss(&mut self) {
+        self.shown_at = None;
+    }
+    // a key press or click clears an info early, an error waits to be dismissed
+    pub fn dismiss_info(&mut self) {
+        if self
+            .log
+            .back()
+            .is_some_and(|last| last.level == Level::Info)
+        {
+            self.shown_at = None;
+        }
+    }
+    // newest first
+    pub fn log(&self) -> impl Iterator<Item = &Notification> {
+        self.log.iter().rev()
+    }
+    pub fn len(&self) -> usize {
+        self.log.len()
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    env, fs,
+    path::{Component, Path, PathBuf},
+};
+
+// the most folder entries looked at to count the audio files of a typed path
+pub const COUNT_ENTRIES_LIMIT: usize = 20_000;
+
+// a typed path as the shell would read it: "~" is the home folder, "$VAR" and "${VAR}" are
+// environment variables, relative paths are from the working folder, "." and ".." are resolved
+pub fn expand(input: &str) -> PathBuf {
+    let input = input.trim();
+    let expanded = expand_vars(input);
+    let expanded = match expanded.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", env::var("HOME").unwrap_or_default(), rest)
+        }
+        _ => expanded,
+    };
+    let path = Path::new(&expanded);
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir().unwrap_or_default().join(path)
+    };
+    normalize(&path)
+}
+
+// unknown variables are left as typed
+fn expand_vars(input: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = input;
+    while let Some(i) = rest.find('$') {
+        expanded.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        match env::var(name) {
+            Ok(value) if !name.is_empty() => expanded.push_str(&value),
+            _ => expanded.push_str(&rest[i..i + 1 + len]),
+        }
+        rest = &after[len..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+// without touching the disk, so symlinked folders keep their names
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+// folders in the folder of `path` that start with its last component, ending with '/',
+// the typed folder is kept as typed so "~/Mu" completes to "~/Music/"
+pub fn folder_completions(path: &str) -> Vec<String> {
+    let (folder, prefix) = match path.rfind('/') {
+        Some(i) => (&path[..=i], &path[i + 1..]),
+        None => ("", path),
+    };
+    let read_dir = match fs::read_dir(expand(if folder.is_empty() { "." } else { folder })) {
+        Ok(read_dir) => read_dir,
+        Err(_) => return vec![],
+    };
+    let mut candidates: Vec<String> = read_dir
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // hidden folders only when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            Some(format!("{}{}/", folder, name))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+// the audio files under `folder`, and false if it stopped looking after `limit` entries
+pub fn count_audio_files(
+    folder: &Path,
+    recursive: bool,
+    extensions: &[String],
+    limit: usize,
+) -> (usize, bool) {
+    let mut count = 0;
+    let mut budget = limit;
+    let mut folders = vec![folder.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        let read_dir = match fs::read_dir(&folder) {
+            Ok(read_dir) => read_dir,
+            Err(_) => continue,
+        };
+        for dir_entry in read_dir.flatten() {
+            if budget == 0 {
+                return (count, false);
+            }
+            budget -= 1;
+            let path = dir_entry.path();
+            if path.is_file() {
+                let extension = path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .unwrap_or_default()
+                    .to_lowercase();
+                if extensions.contains(&extension) {
+                    count += 1;
+                }
+            } else if recursive && path.is_dir() && !path.is_symlink() {
+                folders.push(path);
+            }
+        }
+    }
+    (count, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch_dir;
+
+    #[test]
+    fn tilde_is_the_home_folder() {
+        let home = PathBuf::from(env::var("HOME").unwrap());
+        assert_eq!(expand("~"), home);
+        assert_eq!(expand(" ~/Music "), home.join("Music"));
+        // only a leading "~/" is the home folder
+        assert_eq!(expand("/a/~b"), PathBuf::from("/a/~b"));
+    }
+
+    #[test]
+    fn variables_are_replaced_and_unknown_ones_kept() {
+        env::set_var("SONGBREEZE_TEST_MUSIC", "/srv/music");
+        assert_eq!(
+            expand("$SONGBREEZE_TEST_MUSIC/rock"),
+            PathBuf::from("/srv/music/rock")
+        );
+        assert_eq!(
+            expand("${SONGBREEZE_TEST_MUSIC}_old"),
+            PathBuf::from("/srv/music_old")
+        );
+        assert_eq!(
+            expand("/x/$SONGBREEZE_TEST_UNSET/y"),
+            PathBuf::from("/x/$SONGBREEZE_TEST_UNSET/y")
+        );
+        assert_eq!(expand("/x/${unclosed"), PathBuf::from("/x/${unclosed"));
+        assert_eq!(expand("/x/$"), PathBuf::from("/x/$"));
+    }
+
+    #[test]
+    fn relative_paths_are_from_the_working_folder() {
+        let current_dir = env::current_dir().unwrap();
+        assert_eq!(expand("music"), current_dir.join("music"));
+        assert_eq!(expand("./a/../b"), current_dir.join("b"));
+        assert_eq!(expand("/a/./b/../c/"), PathBuf::from("/a/c"));
+    }
+
+    #[test]
+    fn completions_are_folders_starting_with_the_typed_name() {
+        let dir = scratch_dir("paths-completions");
+        for folder in ["Music", "Musicals", "Podcasts", ".hidden"] {
+            fs::create_dir(dir.join(folder)).unwrap();
+        }
+        fs::write(dir.join("Music.txt"), "").unwrap();
+        let typed = format!("{}/Mu", dir.display());
+        assert_eq!(
+            folder_completions(&typed),
+            [
+                format!("{}/Music/", dir.display()),
+                format!("{}/Musicals/", dir.display())
+            ]
+        );
+        // hidden folders only when the name starts with a dot
+        assert_eq!(folder_completions(&format!("{}/", dir.display())).len(), 3);
+        assert_eq!(
+            folder_completions(&format!("{}/.", dir.display())),
+            [format!("{}/.hidden/", dir.display())]
+        );
+        assert!(folder_completions(&format!("{}/nothing/", dir.display())).is_empty());
+        // the typed folder is kept as typed
+        env::set_var("SONGBREEZE_TEST_COMPLETIONS", &dir);
+        assert_eq!(
+            folder_completions("$SONGBREEZE_TEST_COMPLETIONS/Po"),
+            ["$SONGBREEZE_TEST_COMPLETIONS/Podcasts/"]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn audio_files_are_counted_by_extension() {
+        let dir = scratch_dir("paths-count");
+        fs::create_dir(dir.join("sub")).unwrap();
+        for file in ["a.mp3", "b.MP3", "c.txt", "sub/d.wav"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let extensions = ["mp3".to_string(), "wav".to_string()];
+        let count = |recursive, limit| count_audio_files(&dir, recursive, &extensions, limit);
+        assert_eq!(count(false, COUNT_ENTRIES_LIMIT), (2, true));
+        assert_eq!(count(true, COUNT_ENTRIES_LIMIT), (3, true));
+        // stopped early, the count is not all of them
+        assert!(!count(true, 2).1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
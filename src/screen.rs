@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use tui::layout::{Constraint, Direction, Layout, Margin, Rect};
+
+// the smallest main board worth drawing, the bars give way before it does
+const MAIN_MIN_HEIGHT: u16 = 5;
+const PLAYLISTS_MIN_WIDTH: u16 = 12;
+const CHART_MIN_HEIGHT: u16 = 6;
+const CHART_MAX_HEIGHT: u16 = 14;
+
+// "layout" of setting.json, missing keys fall back to the defaults
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayoutSettings {
+    pub playlists_width: u16, // percent of the home tab, kept between a minimum and half of it
+    pub show_menu_bar: bool,
+    pub show_playlists: bool,
+    pub show_player_bar: bool,
+    pub show_hint_bar: bool,
+    pub compact_width: u16, // the compact layout is used below this size
+    pub compact_height: u16,
+}
+impl Default for LayoutSettings {
+    fn default() -> Self {
+        Self {
+            playlists_width: 20,
+            show_menu_bar: true,
+            show_playlists: true,
+            show_player_bar: true,
+            show_hint_bar: true,
+            compact_width: 80,
+            compact_height: 20,
+        }
+    }
+}
+impl LayoutSettings {
+    pub fn is_compact(&self, area: Rect) -> bool {
+        area.width < self.compact_width || area.height < self.compact_height
+    }
+}
+
+// the boards of the whole screen, hidden bars are None
+pub struct ScreenBoards {
+    pub menu: Option<Rect>,
+    pub main: Rect,
+    pub hints: Option<Rect>, // the keys of the focused view, above the player bar
+    pub player: Option<Rect>,
+    pub compact: bool, // bars are a single line without borders
+}
+
+impl ScreenBoards {
+    pub fn new(area: Rect, layout: &LayoutSettings) -> Self {
+        let compact = layout.is_compact(area);
+        let (menu_height, player_height) = if compact { (1, 1) } else { (3, 4) };
+        let menu_height = if layout.show_menu_bar { menu_height } else { 0 };
+        let player_height = if layout.show_player_bar {
+            player_height
+        } else {
+            0
+        };
+        // the compact layout has no room for hints
+        let hints_height = if layout.show_hint_bar && !compact {
+            1
+        } else {
+            0
+        };
+        let boards = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Length(menu_height),
+                    Constraint::Min(MAIN_MIN_HEIGHT),
+                    Constraint::Length(hints_height),
+                    Constraint::Length(player_height),
+                ]
+                .as_ref(),
+            )
+            .split(area);
+        Self {
+            menu: Some(boards[0]).filter(|_| menu_height > 0),
+            main: boards[1],
+            hints: Some(boards[2]).filter(|_| hints_height > 0),
+            player: Some(boards[3]).filter(|_| player_height > 0),
+            compact,
+        }
+    }
+}
+
+// the playlists and songs boards of the home tab, None when a pane is not shown
+pub fn split_home(area: Rect, layout: &LayoutSettings) -> (Option<Rect>, Rect) {
+    if !layout.show_playlists {
+        return (None, area);
+    }
+    let playlists_width =
+        (u32::from(area.width) * u32::from(layout.playlists_width.min(100)) / 100) as u16;
+    let playlists_width = playlists_width.max(PLAYLISTS_MIN_WIDTH).min(area.width / 2);
+    let boards = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(playlists_width), Constraint::Min(0)].as_ref())
+        .split(area);
+    (Some(boards[0]), boards[1])
+}
+
+// the summary, top lists and chart boards of the stats tab, no chart when it would not fit
+pub fn split_stats(area: Rect, compact: bool) -> (Rect, Rect, Option<Rect>) {
+    let lists_and_chart_height = area.height.saturating_sub(2);
+    let chart_height = if compact || lists_and_chart_height < CHART_MIN_HEIGHT * 2 {
+        0
+    } else {
+        (lists_and_chart_height / 2).clamp(CHART_MIN_HEIGHT, CHART_MAX_HEIGHT)
+    };
+    let boards = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(2),
+                Constraint::Min(0),
+                Constraint::Length(chart_height),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+    (
+        boards[0],
+        boards[1],
+        Some(boards[2]).filter(|_| chart_height > 0),
+    )
+}
+
+// a `width` x `height` board in the middle of `area`, shrunk to fit it
+pub fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+// the board of an overlay view drawn over the main board
+pub fn overlay_rect(main_board: Rect, compact: bool) -> Rect {
+    if compact {
+        main_board
+    } else {
+        main_board.inner(&Margin {
+            vertical: 1,
+            horizontal: 2,
+        })
+    }
+}
@@ -0,0 +1,231 @@
+// fuzzy search
+// 1. '/' filters the focused list (playlists, songs or sources) while typing
+// 2. 'ctrl+f' searches title/artist/album/path of every song in every playlist
+// 3. 'n'/'N' cycle through the hits of the last search
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SearchScope {
+    Playlists,
+    Songs(usize), // playlist idx
+    Sources,
+    Library,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SearchHit {
+    Playlist(usize),
+    Song(usize, usize), // (playlist idx, song idx)
+    Source(usize),
+}
+
+pub struct Search {
+    pub scope: SearchScope,
+    pub hits: Vec<SearchHit>,
+    pub current: usize,
+    // where the user was before searching, restored on cancel
+    pub origin_tab_idx: Option<usize>,
+    pub origin: Option<SearchHit>,
+}
+impl Search {
+    pub fn new(
+        scope: SearchScope,
+        origin_tab_idx: Option<usize>,
+        origin: Option<SearchHit>,
+    ) -> Self {
+        Self {
+            scope,
+            hits: vec![],
+            current: 0,
+            origin_tab_idx,
+            origin,
+        }
+    }
+}
+impl Search {
+    // candidates are (hit, searchable fields), a candidate scores as its best field
+    pub fn update(&mut self, query: &str, candidates: Vec<(SearchHit, Vec<String>)>) {
+        let mut scored: Vec<(i64, SearchHit)> = candidates
+            .into_iter()
+            .filter_map(|(hit, fields)| {
+                fields
+                    .iter()
+                    .filter_map(|field| fuzzy_score(query, field))
+                    .max()
+                    .map(|score| (score, hit))
+            })
+            .collect();
+        // a filtered list keeps its order, the library search ranks by score
+        if self.scope == SearchScope::Library {
+            scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        }
+        self.hits = scored.into_iter().map(|(_, hit)| hit).collect();
+        self.current = 0;
+    }
+    pub fn current_hit(&self) -> Option<SearchHit> {
+        self.hits.get(self.current).copied()
+    }
+    pub fn next_hit(&mut self) -> Option<SearchHit> {
+        if self.hits.is_empty() {
+            return None;
+        }
+        self.current = (self.current + 1) % self.hits.len();
+        self.current_hit()
+    }
+    pub fn previous_hit(&mut self) -> Option<SearchHit> {
+        if self.hits.is_empty() {
+            return None;
+        }
+        self.current = if self.current == 0 {
+            self.hits.len() - 1
+        } else {
+            self.current - 1
+        };
+        self.current_hit()
+    }
+    pub fn contains(&self, hit: SearchHit) -> bool {
+        self.hits.contains(&hit)
+    }
+}
+
+// case insensitive subsequence match, None if `query` does not match `candidate`.
+// consecutive chars and chars at the start of a word score higher
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let mut score = 0;
+    let mut query_idx = 0;
+    let mut last_match: Option<usize> = None;
+    let mut prev_char: Option<char> = None;
+    for (idx, c) in candidate.chars().flat_map(char::to_lowercase).enumerate() {
+        if query_idx < query.len() && c == query[query_idx] {
+            score += 1;
+            match last_match {
+                Some(last) if last + 1 == idx => score += 5,
+                Some(last) => score -= (idx - last - 1).min(3) as i64,
+                None => score -= idx.min(5) as i64,
+            }
+            if !prev_char.is_some_and(|p| p.is_alphanumeric()) {
+                score += 3;
+            }
+            last_match = Some(idx);
+            query_idx += 1;
+        }
+        prev_char = Some(c);
+    }
+    if query_idx == query.len() {
+        Some(score)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library_search(query: &str, candidates: &[&str]) -> Vec<usize> {
+        let mut search = Search::new(SearchScope::Library, None, None);
+        let candidates = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (SearchHit::Playlist(i), vec![c.to_string()]))
+            .collect();
+        search.update(query, candidates);
+        search
+            .hits
+            .iter()
+            .map(|hit| match hit {
+                SearchHit::Playlist(i) => *i,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn query_matches_as_a_subsequence() {
+        assert!(fuzzy_score("bln", "blue monday").is_some());
+        assert!(fuzzy_score("blue mon", "blue monday").is_some());
+        assert!(fuzzy_score("mb", "blue monday").is_none());
+        assert!(fuzzy_score("blues", "blue").is_none());
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(fuzzy_score("  ", ""), Some(0));
+    }
+
+    #[test]
+    fn case_is_ignored_both_ways() {
+        assert_eq!(fuzzy_score("ABC", "abc"), fuzzy_score("abc", "abc"));
+        assert_eq!(fuzzy_score("abc", "ABC"), fuzzy_score("abc", "abc"));
+        assert!(fuzzy_score("É", "été").is_some());
+    }
+
+    #[test]
+    fn prefix_beats_contiguous_beats_scattered() {
+        let prefix = fuzzy_score("abc", "abcx").unwrap();
+        let contiguous = fuzzy_score("abc", "xabc").unwrap();
+        let scattered = fuzzy_score("abc", "axbxc").unwrap();
+        assert!(prefix > contiguous);
+        assert!(contiguous > scattered);
+        // the start of a word beats the middle of one
+        assert!(fuzzy_score("abc", "xx abc") > fuzzy_score("abc", "xxxabc"));
+    }
+
+    #[test]
+    fn library_hits_are_ranked_and_filters_keep_their_order() {
+        let candidates = ["axbxc", "no", "abcx", "xabc"];
+        assert_eq!(library_search("abc", &candidates), [2, 3, 0]);
+        let mut search = Search::new(SearchScope::Playlists, None, None);
+        let candidates = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (SearchHit::Playlist(i), vec![c.to_string()]))
+            .collect();
+        search.update("abc", candidates);
+        assert!(search.hits == [0, 2, 3].map(SearchHit::Playlist));
+    }
+
+    #[test]
+    fn a_candidate_scores_as_its_best_field() {
+        let mut search = Search::new(SearchScope::Library, None, None);
+        search.update(
+            "abc",
+            vec![
+                (SearchHit::Song(0, 0), vec!["axbxc".to_string()]),
+                (
+                    SearchHit::Song(0, 1),
+                    vec!["nothing".to_string(), "abc".to_string()],
+                ),
+            ],
+        );
+        assert!(search.hits == [SearchHit::Song(0, 1), SearchHit::Song(0, 0)]);
+    }
+
+    #[test]
+    fn next_and_previous_wrap_around() {
+        let mut search = Search::new(SearchScope::Library, Some(1), Some(SearchHit::Source(0)));
+        assert!(search.next_hit().is_none());
+        assert!(search.previous_hit().is_none());
+        search.hits = (0..3).map(SearchHit::Playlist).collect();
+        assert!(search.current_hit() == Some(SearchHit::Playlist(0)));
+        assert!(search.previous_hit() == Some(SearchHit::Playlist(2)));
+        assert!(search.next_hit() == Some(SearchHit::Playlist(0)));
+        assert!(search.next_hit() == Some(SearchHit::Playlist(1)));
+        assert!(search.next_hit() == Some(SearchHit::Playlist(2)));
+        assert!(search.next_hit() == Some(SearchHit::Playlist(0)));
+        // a new query starts over from the first hit, the origin is kept for cancel
+        search.current = 2;
+        search.update("", vec![(SearchHit::Source(1), vec![String::new()])]);
+        assert!(search.current_hit() == Some(SearchHit::Source(1)));
+        assert!(search.origin == Some(SearchHit::Source(0)));
+        assert_eq!(search.origin_tab_idx, Some(1));
+    }
+}
@@ -0,0 +1,347 @@
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{columns::SongColumn, screen::LayoutSettings, theme, PlayMode};
+
+// setting.json, missing keys fall back to the defaults
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub play_mode: PlayMode, // play mode at startup
+    pub volume: u8,          // 0 to 100
+    pub scan_recursive: bool,
+    pub scan_extensions: Vec<String>,
+    pub startup_tab: String, // tab title
+    pub confirm_on_quit: bool,
+    pub song_columns: Vec<SongColumn>,
+    pub theme: String, // a built-in theme or a file of the themes folder
+    pub layout: LayoutSettings,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            play_mode: PlayMode::ListLoop,
+            volume: 100,
+            scan_recursive: false,
+            scan_extensions: vec!["mp3".to_string(), "wav".to_string()],
+            startup_tab: "Home".to_string(),
+            confirm_on_quit: false,
+            song_columns: SongColumn::default_columns(),
+            theme: "dark".to_string(),
+            layout: LayoutSettings::default(),
+        }
+    }
+}
+impl Settings {
+    pub fn load(path: &Path) -> Self {
+        let mut settings: Settings = fs::read_to_string(path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        // the songs table and sorting need a column
+        if settings.song_columns.is_empty() {
+            settings.song_columns = SongColumn::default_columns();
+        }
+        settings
+    }
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self).unwrap())
+    }
+}
+
+// how a setting is edited in the settings tab
+pub enum SettingKind {
+    Toggle, // Enter flips it
+    Cycle,  // Enter moves to the next choice
+    Input,  // Enter opens the edit popup
+}
+
+// rows of the settings tab
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SettingItem {
+    PlayMode,
+    Volume,
+    ScanRecursive,
+    ScanExtensions,
+    StartupTab,
+    ConfirmOnQuit,
+    SongColumns,
+    Theme,
+    PlaylistsWidth,
+    ShowMenuBar,
+    ShowPlaylists,
+    ShowPlayerBar,
+    ShowHintBar,
+    CompactSize,
+}
+impl SettingItem {
+    pub const ALL: [SettingItem; 14] = [
+        SettingItem::PlayMode,
+        SettingItem::Volume,
+        SettingItem::ScanRecursive,
+        SettingItem::ScanExtensions,
+        SettingItem::StartupTab,
+        SettingItem::ConfirmOnQuit,
+        SettingItem::SongColumns,
+        SettingItem::Theme,
+        SettingItem::PlaylistsWidth,
+        SettingItem::ShowMenuBar,
+        SettingItem::ShowPlaylists,
+        SettingItem::ShowPlayerBar,
+        SettingItem::ShowHintBar,
+        SettingItem::CompactSize,
+    ];
+    pub fn title(&self) -> &'static str {
+        match self {
+            SettingItem::PlayMode => "Default play mode",
+            SettingItem::Volume => "Volume",
+            SettingItem::ScanRecursive => "Scan subfolders of sources (next scan)",
+            SettingItem::ScanExtensions => "Audio file extensions (next scan)",
+            SettingItem::StartupTab => "Startup tab",
+            SettingItem::ConfirmOnQuit => "Confirm on quit",
+            SettingItem::SongColumns => "Song columns",
+            SettingItem::Theme => "Theme",
+            SettingItem::PlaylistsWidth => "Playlists width (%)",
+            SettingItem::ShowMenuBar => "Show menu bar",
+            SettingItem::ShowPlaylists => "Show playlists",
+            SettingItem::ShowPlayerBar => "Show player bar",
+            SettingItem::ShowHintBar => "Show key hints",
+            SettingItem::CompactSize => "Compact layout below (columns x rows)",
+        }
+    }
+    pub fn kind(&self) -> SettingKind {
+        match self {
+            SettingItem::ScanRecursive
+            | SettingItem::ConfirmOnQuit
+            | SettingItem::ShowMenuBar
+            | SettingItem::ShowPlaylists
+            | SettingItem::ShowPlayerBar
+            | SettingItem::ShowHintBar => SettingKind::Toggle,
+            SettingItem::PlayMode | SettingItem::StartupTab | SettingItem::Theme => {
+                SettingKind::Cycle
+            }
+            SettingItem::Volume
+            | SettingItem::ScanExtensions
+            | SettingItem::SongColumns
+            | SettingItem::PlaylistsWidth
+            | SettingItem::CompactSize => SettingKind::Input,
+        }
+    }
+    pub fn value_text(&self, settings: &Settings) -> String {
+        let yes_no = |b: bool| if b { "yes" } else { "no" }.to_string();
+        match self {
+            SettingItem::PlayMode => settings.play_mode.title().to_string(),
+            SettingItem::Volume => settings.volume.to_string(),
+            SettingItem::ScanRecursive => yes_no(settings.scan_recursive),
+            SettingItem::ScanExtensions => settings.scan_extensions.join(", "),
+            SettingItem::StartupTab => settings.startup_tab.clone(),
+            SettingItem::ConfirmOnQuit => yes_no(settings.confirm_on_quit),
+            SettingItem::SongColumns => settings
+                .song_columns
+                .iter()
+                .map(|c| {
+                    serde_json::to_value(c)
+                        .unwrap()
+                        .as_str()
+                        .unwrap()
+                        .to_string()
+                })
+                .collect::<Vec<String>>()
+                .join(", "),
+            SettingItem::Theme if theme::no_color() => {
+                format!("{} (NO_COLOR is set, monochrome)", settings.theme)
+            }
+            SettingItem::Theme => settings.theme.clone(),
+            SettingItem::PlaylistsWidth => settings.layout.playlists_width.to_string(),
+            SettingItem::ShowMenuBar => yes_no(settings.layout.show_menu_bar),
+            SettingItem::ShowPlaylists => yes_no(settings.layout.show_playlists),
+            SettingItem::ShowPlayerBar => yes_no(settings.layout.show_player_bar),
+            SettingItem::ShowHintBar => yes_no(settings.layout.show_hint_bar),
+            SettingItem::CompactSize => format!(
+                "{}x{}",
+                settings.layout.compact_width, settings.layout.compact_height
+            ),
+        }
+    }
+    // for Toggle and Cycle settings, `tab_titles` and `theme_names` are the choices
+    pub fn advance(&self, settings: &mut Settings, tab_titles: &[String], theme_names: &[String]) {
+        let next_choice = |current: &String, choices: &[String]| {
+            let i = choices.iter().position(|c| c == current);
+            choices[i.map_or(0, |i| (i + 1) % choices.len())].clone()
+        };
+        match self {
+            SettingItem::PlayMode => settings.play_mode = settings.play_mode.next(),
+            SettingItem::ScanRecursive => settings.scan_recursive = !settings.scan_recursive,
+            SettingItem::StartupTab => {
+                settings.startup_tab = next_choice(&settings.startup_tab, tab_titles)
+            }
+            SettingItem::Theme => settings.theme = next_choice(&settings.theme, theme_names),
+            SettingItem::ConfirmOnQuit => settings.confirm_on_quit = !settings.confirm_on_quit,
+            SettingItem::ShowMenuBar => {
+                settings.layout.show_menu_bar = !settings.layout.show_menu_bar
+            }
+            SettingItem::ShowPlaylists => {
+                settings.layout.show_playlists = !settings.layout.show_playlists
+            }
+            SettingItem::ShowPlayerBar => {
+                settings.layout.show_player_bar = !settings.layout.show_player_bar
+            }
+            SettingItem::ShowHintBar => {
+                settings.layout.show_hint_bar = !settings.layout.show_hint_bar
+            }
+            SettingItem::Volume
+            | SettingItem::ScanExtensions
+            | SettingItem::SongColumns
+            | SettingItem::PlaylistsWidth
+            | SettingItem::CompactSize => {}
+        }
+    }
+    // for Input settings, false if the input is not valid
+    pub fn apply_input(&self, settings: &mut Settings, input: &str) -> bool {
+        let list: Vec<String> = input
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        match self {
+            SettingItem::Volume => match input.trim().parse::<u8>() {
+                Ok(volume) if volume <= 100 => settings.volume = volume,
+                _ => return false,
+            },
+            SettingItem::PlaylistsWidth => match input.trim().parse::<u16>() {
+                Ok(width) if (10..=50).contains(&width) => settings.layout.playlists_width = width,
+                _ => return false,
+            },
+            SettingItem::CompactSize => {
+                let size = input
+                    .split_once('x')
+                    .map(|(w, h)| (w.trim().parse::<u16>(), h.trim().parse::<u16>()));
+                match size {
+                    Some((Ok(width), Ok(height))) => {
+                        settings.layout.compact_width = width;
+                        settings.layout.compact_height = height;
+                    }
+                    _ => return false,
+                }
+            }
+            SettingItem::ScanExtensions if !list.is_empty() => {
+                settings.scan_extensions = list
+                    .into_iter()
+                    .map(|e| e.trim_start_matches('.').to_lowercase())
+                    .collect();
+            }
+            SettingItem::SongColumns if !list.is_empty() => {
+                let columns: Result<Vec<SongColumn>, _> = list
+                    .into_iter()
+                    .map(|c| serde_json::from_value(serde_json::Value::String(c)))
+                    .collect();
+                match columns {
+                    Ok(columns) => settings.song_columns = columns,
+                    Err(_) => return false,
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the setting after the input, None if the input was refused
+    fn applied(item: SettingItem, input: &str) -> Option<String> {
+        let mut settings = Settings::default();
+        item.apply_input(&mut settings, input)
+            .then(|| item.value_text(&settings))
+    }
+
+    #[test]
+    fn volume_is_a_number_up_to_100() {
+        assert_eq!(applied(SettingItem::Volume, " 42 "), Some("42".to_string()));
+        assert_eq!(applied(SettingItem::Volume, "100"), Some("100".to_string()));
+        assert_eq!(applied(SettingItem::Volume, "101"), None);
+        assert_eq!(applied(SettingItem::Volume, "-1"), None);
+        assert_eq!(applied(SettingItem::Volume, "loud"), None);
+        assert_eq!(applied(SettingItem::Volume, ""), None);
+    }
+
+    #[test]
+    fn layout_sizes_are_numbers_in_range() {
+        assert_eq!(
+            applied(SettingItem::PlaylistsWidth, "30"),
+            Some("30".to_string())
+        );
+        assert_eq!(applied(SettingItem::PlaylistsWidth, "9"), None);
+        assert_eq!(applied(SettingItem::PlaylistsWidth, "51"), None);
+        assert_eq!(applied(SettingItem::PlaylistsWidth, "wide"), None);
+        assert_eq!(
+            applied(SettingItem::CompactSize, " 80 x 24 "),
+            Some("80x24".to_string())
+        );
+        assert_eq!(applied(SettingItem::CompactSize, "80"), None);
+        assert_eq!(applied(SettingItem::CompactSize, "80xtall"), None);
+        assert_eq!(applied(SettingItem::CompactSize, "70000x24"), None);
+    }
+
+    #[test]
+    fn extensions_are_trimmed_and_lowercased() {
+        assert_eq!(
+            applied(SettingItem::ScanExtensions, ".MP3, flac,,"),
+            Some("mp3, flac".to_string())
+        );
+        assert_eq!(applied(SettingItem::ScanExtensions, " , "), None);
+    }
+
+    #[test]
+    fn song_columns_are_known_names() {
+        assert_eq!(
+            applied(SettingItem::SongColumns, "title, track_number"),
+            Some("title, track_number".to_string())
+        );
+        assert_eq!(applied(SettingItem::SongColumns, "title, bogus"), None);
+        assert_eq!(applied(SettingItem::SongColumns, ""), None);
+    }
+
+    #[test]
+    fn only_input_settings_take_input() {
+        assert_eq!(applied(SettingItem::ConfirmOnQuit, "yes"), None);
+        assert_eq!(applied(SettingItem::StartupTab, "Home"), None);
+    }
+
+    #[test]
+    fn startup_tab_cycles_through_the_tabs() {
+        let tabs = ["Home".to_string(), "Sources".to_string()];
+        let mut settings = Settings::default();
+        SettingItem::StartupTab.advance(&mut settings, &tabs, &[]);
+        assert_eq!(settings.startup_tab, "Sources");
+        SettingItem::StartupTab.advance(&mut settings, &tabs, &[]);
+        assert_eq!(settings.startup_tab, "Home");
+        settings.startup_tab = "Gone".to_string();
+        SettingItem::StartupTab.advance(&mut settings, &tabs, &[]);
+        assert_eq!(settings.startup_tab, "Home");
+    }
+
+    #[test]
+    fn an_unknown_theme_moves_to_the_first_one() {
+        let themes = ["dark".to_string(), "light".to_string()];
+        let mut settings = Settings::default();
+        SettingItem::Theme.advance(&mut settings, &[], &themes);
+        assert_eq!(settings.theme, "light");
+        settings.theme = "deleted".to_string();
+        SettingItem::Theme.advance(&mut settings, &[], &themes);
+        assert_eq!(settings.theme, "dark");
+        // themes are picked from the list, not typed
+        assert_eq!(applied(SettingItem::Theme, "light"), None);
+        assert_eq!(applied(SettingItem::Theme, "no such theme"), None);
+    }
+
+    #[test]
+    fn missing_keys_keep_their_defaults() {
+        let settings: Settings = serde_json::from_str(r#"{ "volume": 30 }"#).unwrap();
+        assert_eq!(settings.volume, 30);
+        assert!(settings.song_columns == SongColumn::default_columns());
+    }
+}
@@ -0,0 +1,315 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::paths;
+
+// a folder songs are scanned from, and what the last scan found there
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Source {
+    pub path: String,
+    pub enabled: bool,           // disabled sources are kept but not scanned
+    pub recursive: Option<bool>, // None follows the scan setting
+    pub excludes: Vec<String>,   // "*" and "?" patterns, of names or of paths below the source
+    pub label: Option<String>,
+    pub last_scan: Option<u64>, // unix timestamp in seconds
+    pub tracks: usize,
+    pub size: u64, // bytes
+}
+impl Default for Source {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            enabled: true,
+            recursive: None,
+            excludes: vec![],
+            label: None,
+            last_scan: None,
+            tracks: 0,
+            size: 0,
+        }
+    }
+}
+impl Source {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            ..Self::default()
+        }
+    }
+}
+impl Source {
+    pub fn display_name(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.path)
+    }
+    pub fn is_recursive(&self, default_recursive: bool) -> bool {
+        self.recursive.unwrap_or(default_recursive)
+    }
+    // false when the folder is gone, or on a drive that is not mounted
+    pub fn is_reachable(&self) -> bool {
+        paths::expand(&self.path).is_dir()
+    }
+    // a pattern with a '/' is matched against the path below the source, others against each name
+    pub fn is_excluded(&self, relative_path: &Path) -> bool {
+        let relative = relative_path.to_string_lossy();
+        self.excludes.iter().any(|pattern| {
+            if pattern.contains('/') {
+                glob_match(pattern.trim_matches('/'), &relative)
+            } else {
+                relative_path
+                    .iter()
+                    .any(|name| glob_match(pattern, &name.to_string_lossy()))
+            }
+        })
+    }
+    // whether a scan of the source finds the file, the extension aside
+    pub fn covers(&self, song_path: &Path, default_recursive: bool) -> bool {
+        let root = paths::expand(&self.path);
+        match song_path.strip_prefix(&root) {
+            Ok(relative) => {
+                (self.is_recursive(default_recursive) || relative.components().count() == 1)
+                    && !self.is_excluded(relative)
+            }
+            Err(_) => false,
+        }
+    }
+    // the audio files of the source by extension, left out if excluded
+    pub fn scan(&self, default_recursive: bool, extensions: &[String]) -> Vec<PathBuf> {
+        let root = paths::expand(&self.path);
+        let recursive = self.is_recursive(default_recursive);
+        let mut song_paths = vec![];
+        let mut folders = vec![root.clone()];
+        while let Some(folder) = folders.pop() {
+            let read_dir = match fs::read_dir(&folder) {
+                Ok(read_dir) => read_dir,
+                Err(_) => continue,
+            };
+            for dir_entry in read_dir.flatten() {
+                let path = dir_entry.path();
+                if self.is_excluded(path.strip_prefix(&root).unwrap_or(&path)) {
+                    continue;
+                }
+                if path.is_file() {
+                    let extension = path
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .unwrap_or_default()
+                        .to_lowercase();
+                    if extensions.contains(&extension) {
+                        song_paths.push(path);
+                    }
+                } else if recursive && path.is_dir() && !path.is_symlink() {
+                    folders.push(path);
+                }
+            }
+        }
+        song_paths
+    }
+}
+
+// a source about to be removed, with the songs it takes away: the ones under it that no other
+// enabled source covers
+pub struct SourceRemoval {
+    pub source_idx: usize,
+    pub name: String,
+    pub library_tracks: usize, // of the default playlist, they go in any case
+    pub playlist_entries: usize, // of the other playlists, pruned or kept
+    pub playlists: usize,
+    source: Source,
+    others: Vec<Source>,
+    default_recursive: bool,
+}
+impl SourceRemoval {
+    pub fn new(sources: &[Source], source_idx: usize, default_recursive: bool) -> Self {
+        let source = sources[source_idx].clone();
+        Self {
+            source_idx,
+            name: source.display_name().to_string(),
+            library_tracks: 0,
+            playlist_entries: 0,
+            playlists: 0,
+            others: sources
+                .iter()
+                .enumerate()
+                .filter(|(i, other)| *i != source_idx && other.enabled)
+                .map(|(_, other)| other.clone())
+                .collect(),
+            source,
+            default_recursive,
+        }
+    }
+}
+impl SourceRemoval {
+    pub fn takes(&self, song_path: &Path) -> bool {
+        self.source.covers(song_path, self.default_recursive)
+            && !self
+                .others
+                .iter()
+                .any(|other| other.covers(song_path, self.default_recursive))
+    }
+}
+
+// "*" is any run of characters, "?" is any one
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // where the last '*' was, and the text position it is matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // let the '*' take one more character
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// source json
+#[derive(Serialize, Deserialize)]
+pub struct SourceDB {
+    #[serde(deserialize_with = "deserialize_sources")]
+    pub sources: Vec<Source>,
+}
+impl SourceDB {
+    pub fn new_empty() -> Self {
+        Self { sources: vec![] }
+    }
+}
+
+// sources used to be plain paths, they become records with the default options
+fn deserialize_sources<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Source>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StoredSource {
+        Path(String),
+        Record(Source),
+    }
+    let stored = Vec::<StoredSource>::deserialize(deserializer)?;
+    Ok(stored
+        .into_iter()
+        .map(|source| match source {
+            StoredSource::Path(path) => Source::new(path),
+            StoredSource::Record(source) => source,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch_dir;
+
+    fn excluding(patterns: &[&str]) -> Source {
+        Source {
+            excludes: patterns.iter().map(|p| p.to_string()).collect(),
+            ..Source::new("/music".to_string())
+        }
+    }
+
+    #[test]
+    fn plain_paths_of_the_old_format_become_records() {
+        let source_db: SourceDB = serde_json::from_str(r#"{ "sources": ["/a", "/b"] }"#).unwrap();
+        let paths: Vec<&str> = source_db.sources.iter().map(|s| s.path.as_str()).collect();
+        assert_eq!(paths, ["/a", "/b"]);
+        assert!(source_db
+            .sources
+            .iter()
+            .all(|s| s.enabled && s.recursive.is_none()));
+    }
+
+    #[test]
+    fn old_and_new_entries_can_be_mixed() {
+        let source_db: SourceDB = serde_json::from_str(
+            r#"{ "sources": ["/a", { "path": "/b", "enabled": false, "label": "B" }] }"#,
+        )
+        .unwrap();
+        assert_eq!(source_db.sources[0].display_name(), "/a");
+        assert!(!source_db.sources[1].enabled);
+        assert_eq!(source_db.sources[1].display_name(), "B");
+        // missing keys keep their defaults
+        assert!(source_db.sources[1].excludes.is_empty());
+        let saved = serde_json::to_string(&source_db).unwrap();
+        let loaded: SourceDB = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded.sources[1].label.as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn glob_stars_and_question_marks() {
+        assert!(glob_match("*.tmp", "song.tmp"));
+        assert!(glob_match("*.tmp", ".tmp"));
+        assert!(!glob_match("*.tmp", "song.tmp.mp3"));
+        assert!(glob_match("demo?", "demo1"));
+        assert!(!glob_match("demo?", "demo"));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("a*b*c", "axxbyy"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn names_are_matched_at_any_depth_and_paths_from_the_source() {
+        let source = excluding(&["*.tmp", "live/demos/"]);
+        assert!(source.is_excluded(Path::new("a/b/song.tmp")));
+        assert!(source.is_excluded(Path::new("song.tmp/inside.mp3")));
+        assert!(source.is_excluded(Path::new("live/demos")));
+        assert!(!source.is_excluded(Path::new("old/live/demos")));
+        assert!(!source.is_excluded(Path::new("live/song.mp3")));
+        assert!(!excluding(&[]).is_excluded(Path::new("any.mp3")));
+    }
+
+    #[test]
+    fn scan_finds_audio_files_and_leaves_excluded_ones_out() {
+        let dir = scratch_dir("sources-scan");
+        fs::create_dir_all(dir.join("album/skip")).unwrap();
+        for file in ["a.mp3", "b.WAV", "c.txt", "album/d.mp3", "album/skip/e.mp3"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let source = Source {
+            excludes: vec!["skip".to_string()],
+            ..Source::new(dir.to_string_lossy().to_string())
+        };
+        let extensions = ["mp3".to_string(), "wav".to_string()];
+        let scanned = |source: &Source, default_recursive| {
+            let mut names: Vec<String> = source
+                .scan(default_recursive, &extensions)
+                .iter()
+                .map(|p| p.strip_prefix(&dir).unwrap().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(scanned(&source, false), ["a.mp3", "b.WAV"]);
+        assert_eq!(scanned(&source, true), ["a.mp3", "album/d.mp3", "b.WAV"]);
+        // the source option wins over the scan setting
+        let not_recursive = Source {
+            recursive: Some(false),
+            ..source.clone()
+        };
+        assert_eq!(scanned(&not_recursive, true), ["a.mp3", "b.WAV"]);
+        assert!(source.is_reachable());
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(!source.is_reachable());
+        assert!(scanned(&source, true).is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Song;
+
+// per song data that is not in the audio file itself
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SongStats {
+    pub rating: u8, // 0 (unrated) to 5 stars
+    pub favorite: bool,
+    pub play_count: u32,
+    pub skip_count: u32,
+    pub last_played: Option<u64>, // unix timestamp in seconds
+}
+
+// stats.json, shared by the ui thread (ratings, favorites) and the playback thread (counts)
+#[derive(Default, Serialize, Deserialize)]
+pub struct SongStatsDB {
+    songs: HashMap<String, SongStats>, // song path -> stats
+    #[serde(skip)]
+    file_path: PathBuf,
+    // bumped on every change so the ui knows when to refresh smart playlists
+    #[serde(skip)]
+    pub revision: u64,
+}
+impl SongStatsDB {
+    pub fn load(file_path: PathBuf) -> Self {
+        let mut db: SongStatsDB = fs::read_to_string(&file_path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        db.file_path = file_path;
+        db
+    }
+}
+impl SongStatsDB {
+    pub fn get(&self, path: &Path) -> SongStats {
+        self.songs
+            .get(path.to_string_lossy().as_ref())
+            .copied()
+            .unwrap_or_default()
+    }
+    // the change is kept even if stats.json can not be written
+    fn update(&mut self, path: &Path, f: impl FnOnce(&mut SongStats)) -> io::Result<()> {
+        f(self
+            .songs
+            .entry(path.to_string_lossy().to_string())
+            .or_default());
+        self.revision += 1;
+        self.save()
+    }
+    // many songs with a single write
+    fn update_all(&mut self, paths: &[PathBuf], f: impl Fn(&mut SongStats)) -> io::Result<()> {
+        for path in paths {
+            f(self
+                .songs
+                .entry(path.to_string_lossy().to_string())
+                .or_default());
+        }
+        self.revision += 1;
+        self.save()
+    }
+    pub fn record_finished(&mut self, path: &Path) -> io::Result<()> {
+        self.update(path, |stats| {
+            stats.play_count += 1;
+            stats.last_played = Some(now());
+        })
+    }
+    pub fn record_skipped(&mut self, path: &Path) -> io::Result<()> {
+        self.update(path, |stats| {
+            stats.skip_count += 1;
+            stats.last_played = Some(now());
+        })
+    }
+    pub fn set_ratings(&mut self, paths: &[PathBuf], rating: u8) -> io::Result<()> {
+        self.update_all(paths, |stats| stats.rating = rating.min(5))
+    }
+    pub fn set_favorites(&mut self, paths: &[PathBuf], favorite: bool) -> io::Result<()> {
+        self.update_all(paths, |stats| stats.favorite = favorite)
+    }
+    fn save(&self) -> io::Result<()> {
+        fs::write(&self.file_path, serde_json::to_string_pretty(self).unwrap())
+    }
+}
+
+// playlists built from song stats instead of playlist.json
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SmartPlaylist {
+    Favorites,
+    TopRated,
+    MostPlayed,
+}
+impl SmartPlaylist {
+    pub const ALL: [SmartPlaylist; 3] = [
+        SmartPlaylist::Favorites,
+        SmartPlaylist::TopRated,
+        SmartPlaylist::MostPlayed,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            SmartPlaylist::Favorites => "Favorites",
+            SmartPlaylist::TopRated => "Top Rated",
+            SmartPlaylist::MostPlayed => "Most Played",
+        }
+    }
+    // songs of the library that belong to the playlist, best first
+    pub fn select(&self, library: &[Song], stats: &SongStatsDB) -> Vec<Song> {
+        let mut songs_set: HashSet<&PathBuf> = HashSet::new();
+        let mut songs: Vec<(SongStats, &Song)> = library
+            .iter()
+            .filter(|s| songs_set.insert(&s.path))
+            .map(|s| (stats.get(&s.path), s))
+            .filter(|(stats, _)| match self {
+                SmartPlaylist::Favorites => stats.favorite,
+                SmartPlaylist::TopRated => stats.rating >= 4,
+                SmartPlaylist::MostPlayed => stats.play_count > 0,
+            })
+            .collect();
+        match self {
+            SmartPlaylist::Favorites => {}
+            SmartPlaylist::TopRated => songs.sort_by_key(|(stats, _)| Reverse(stats.rating)),
+            SmartPlaylist::MostPlayed => {
+                songs.sort_by_key(|(stats, _)| Reverse(stats.play_count));
+                songs.truncate(50);
+            }
+        }
+        songs.into_iter().map(|(_, s)| s.clone()).collect()
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// "5m ago", "3h ago", "2d ago"
+pub fn format_time_ago(timestamp: u64) -> String {
+    let secs = now().saturating_sub(timestamp);
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(name: &str) -> Song {
+        Song::untagged(&format!("/music/{}", name))
+    }
+
+    // stats set in memory, nothing is written to stats.json
+    fn db(stats: &[(&str, SongStats)]) -> SongStatsDB {
+        let mut db = SongStatsDB::default();
+        for (name, song_stats) in stats {
+            db.songs.insert(format!("/music/{}", name), *song_stats);
+        }
+        db
+    }
+
+    fn names(songs: Vec<Song>) -> Vec<String> {
+        songs.into_iter().map(|s| s.name).collect()
+    }
+
+    #[test]
+    fn favorites_keep_the_library_order() {
+        let favorite = SongStats {
+            favorite: true,
+            ..Default::default()
+        };
+        let library = [song("a.mp3"), song("b.mp3"), song("c.mp3")];
+        let stats = db(&[("c.mp3", favorite), ("a.mp3", favorite)]);
+        assert_eq!(
+            names(SmartPlaylist::Favorites.select(&library, &stats)),
+            ["a.mp3", "c.mp3"]
+        );
+    }
+
+    #[test]
+    fn top_rated_has_four_stars_and_up_best_first() {
+        let rated = |rating| SongStats {
+            rating,
+            ..Default::default()
+        };
+        let library = [song("a.mp3"), song("b.mp3"), song("c.mp3")];
+        let stats = db(&[
+            ("a.mp3", rated(4)),
+            ("b.mp3", rated(3)),
+            ("c.mp3", rated(5)),
+        ]);
+        assert_eq!(
+            names(SmartPlaylist::TopRated.select(&library, &stats)),
+            ["c.mp3", "a.mp3"]
+        );
+    }
+
+    #[test]
+    fn most_played_skips_unplayed_and_duplicate_songs() {
+        let played = |play_count| SongStats {
+            play_count,
+            ..Default::default()
+        };
+        // the same file found by two sources
+        let library = [song("a.mp3"), song("b.mp3"), song("c.mp3"), song("b.mp3")];
+        let stats = db(&[("a.mp3", played(1)), ("b.mp3", played(7))]);
+        assert_eq!(
+            names(SmartPlaylist::MostPlayed.select(&library, &stats)),
+            ["b.mp3", "a.mp3"]
+        );
+    }
+
+    #[test]
+    fn most_played_keeps_the_top_fifty() {
+        let library: Vec<Song> = (0..60).map(|i| song(&format!("{}.mp3", i))).collect();
+        let mut stats = db(&[]);
+        for (i, song) in library.iter().enumerate() {
+            stats.songs.insert(
+                song.path.to_string_lossy().to_string(),
+                SongStats {
+                    play_count: i as u32 + 1,
+                    ..Default::default()
+                },
+            );
+        }
+        let selected = SmartPlaylist::MostPlayed.select(&library, &stats);
+        assert_eq!(selected.len(), 50);
+        assert_eq!(selected[0].name, "59.mp3");
+        assert_eq!(selected[49].name, "10.mp3");
+    }
+}
@@ -0,0 +1,112 @@
+use std::path::Path;
+
+use id3::{Tag, TagLike, Version};
+
+// the tags the tag editor changes, typed as "artist=...; album=..."
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TagField {
+    Title,
+    Artist,
+    Album,
+    Track,
+}
+impl TagField {
+    pub const ALL: [TagField; 4] = [
+        TagField::Title,
+        TagField::Artist,
+        TagField::Album,
+        TagField::Track,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            TagField::Title => "title",
+            TagField::Artist => "artist",
+            TagField::Album => "album",
+            TagField::Track => "track",
+        }
+    }
+}
+
+// a field and its new value, None removes the tag
+pub type TagChange = (TagField, Option<String>);
+
+// "artist=Foo; track=3; album=", fields are separated by ';' since titles often have commas,
+// an empty value removes the tag
+pub fn parse(input: &str) -> Result<Vec<TagChange>, String> {
+    let mut changes: Vec<TagChange> = vec![];
+    for part in input.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+        let (name, value) = part
+            .split_once('=')
+            .ok_or_else(|| format!("{} is not field=value", part))?;
+        let name = name.trim().to_lowercase();
+        let field = TagField::ALL
+            .into_iter()
+            .find(|f| f.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = TagField::ALL.iter().map(|f| f.name()).collect();
+                format!("unknown tag {}, one of {}", name, names.join(", "))
+            })?;
+        let value = value.trim();
+        if field == TagField::Track && !value.is_empty() && value.parse::<u32>().is_err() {
+            return Err(format!("track {} is not a number", value));
+        }
+        // the last one typed wins
+        changes.retain(|(f, _)| *f != field);
+        changes.push((field, Some(value.to_string()).filter(|v| !v.is_empty())));
+    }
+    Ok(changes)
+}
+
+// the input `parse` reads back
+pub fn format(values: &[(TagField, String)]) -> String {
+    values
+        .iter()
+        .map(|(field, value)| format!("{}={}", field.name(), value))
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+// id3 tags sit at the start of mp3 files, other formats would be broken by one
+pub fn is_editable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("mp3"))
+}
+
+// the other tags of the file are kept
+pub fn write(path: &Path, changes: &[TagChange]) -> Result<(), String> {
+    let mut tag = id3::no_tag_ok(Tag::read_from_path(path))
+        .map_err(|e| e.to_string())?
+        .unwrap_or_default();
+    for (field, value) in changes {
+        match (field, value) {
+            (TagField::Title, Some(value)) => tag.set_title(value),
+            (TagField::Title, None) => tag.remove_title(),
+            (TagField::Artist, Some(value)) => tag.set_artist(value),
+            (TagField::Artist, None) => tag.remove_artist(),
+            (TagField::Album, Some(value)) => tag.set_album(value),
+            (TagField::Album, None) => tag.remove_album(),
+            (TagField::Track, Some(value)) => tag.set_track(value.parse().unwrap_or_default()),
+            (TagField::Track, None) => tag.remove_track(),
+        }
+    }
+    tag.write_to_path(path, Version::Id3v24)
+        .map_err(|e| e.to_string())
+}
+
+// the values the file has for `fields`, to write them back later
+pub fn read(path: &Path, fields: &[TagField]) -> Vec<TagChange> {
+    let tag = Tag::read_from_path(path).ok();
+    fields
+        .iter()
+        .map(|field| {
+            let value = tag.as_ref().and_then(|t| match field {
+                TagField::Title => t.title().map(String::from),
+                TagField::Artist => t.artist().map(String::from),
+                TagField::Album => t.album().map(String::from),
+                TagField::Track => t.track().map(|n| n.to_string()),
+            });
+            (*field, value)
+        })
+        .collect()
+}
@@ -0,0 +1,74 @@
+use std::{
+    backtrace::Backtrace,
+    fs::OpenOptions,
+    io::{self, Write},
+    panic,
+    path::PathBuf,
+    process, thread,
+};
+
+use crossterm::{
+    cursor::Show,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+use crate::stats;
+
+// takes the terminal for the ui, and gives it back when dropped, however main returns
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    pub fn new() -> io::Result<Self> {
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+// best effort, there is nothing left to report a failure to
+fn restore_terminal() {
+    let _ = execute!(
+        io::stdout(),
+        DisableMouseCapture,
+        LeaveAlternateScreen,
+        Show
+    );
+    let _ = disable_raw_mode();
+}
+
+// a panic in any thread gives the terminal back, appends a report to the crash log and exits,
+// the ui can not go on without the playback or input thread
+pub fn install_panic_hook(crash_log_file_path: PathBuf) {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        let report = format!(
+            "--- crash at {} in thread '{}' ---\n{}\n{}\n",
+            stats::now(),
+            thread::current().name().unwrap_or("unnamed"),
+            info,
+            Backtrace::force_capture()
+        );
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&crash_log_file_path)
+            .and_then(|mut file| file.write_all(report.as_bytes()));
+        default_hook(info);
+        if written.is_ok() {
+            eprintln!(
+                "songbreeze crashed, the report was written to {}",
+                crash_log_file_path.display()
+            );
+        }
+        process::exit(101);
+    }));
+}
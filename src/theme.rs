@@ -0,0 +1,239 @@
+use std::{env, fs, path::Path};
+
+use serde_json::Value;
+use tui::style::{Color, Modifier, Style};
+
+// named styles the draw code uses instead of color literals
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub text: Style,
+    pub muted: Style,
+    pub accent: Style,    // titles, labels, prompts and popups
+    pub highlight: Style, // selected item of a list
+    pub marked: Style,    // songs marked for a bulk action
+    pub heading: Style,
+    pub value: Style,
+    pub search_hit: Style,
+    pub action: Style,
+    pub danger: Style,
+    pub success: Style,
+    pub error: Style,
+    pub gauge: Style,
+    pub chart: Style,
+    pub chart_value: Style,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        let fg = |color| Style::default().fg(color);
+        Self {
+            text: fg(Color::White),
+            muted: fg(Color::DarkGray),
+            accent: fg(Color::Yellow),
+            highlight: fg(Color::Yellow),
+            marked: Style::default().bg(Color::DarkGray),
+            heading: Style::default().add_modifier(Modifier::BOLD),
+            value: fg(Color::Cyan),
+            search_hit: fg(Color::Cyan),
+            action: fg(Color::Magenta),
+            danger: fg(Color::Red),
+            success: fg(Color::Green),
+            error: fg(Color::Red),
+            gauge: fg(Color::Blue)
+                .bg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            chart: fg(Color::Blue),
+            chart_value: fg(Color::Black).bg(Color::Blue),
+        }
+    }
+    // for terminals with a light background
+    pub fn light() -> Self {
+        let fg = |color| Style::default().fg(color);
+        Self {
+            text: fg(Color::Black),
+            muted: fg(Color::Gray),
+            accent: fg(Color::Magenta),
+            highlight: fg(Color::Blue).add_modifier(Modifier::BOLD),
+            marked: Style::default().bg(Color::Gray),
+            heading: Style::default().add_modifier(Modifier::BOLD),
+            value: fg(Color::Blue),
+            search_hit: fg(Color::Red),
+            action: fg(Color::Magenta),
+            danger: fg(Color::Red),
+            success: fg(Color::Green),
+            error: fg(Color::Red),
+            gauge: fg(Color::Blue).bg(Color::Gray).add_modifier(Modifier::BOLD),
+            chart: fg(Color::Blue),
+            chart_value: fg(Color::White).bg(Color::Blue),
+        }
+    }
+    // modifiers only, used whenever NO_COLOR is set
+    pub fn monochrome() -> Self {
+        let plain = Style::default();
+        let with = |modifier| Style::default().add_modifier(modifier);
+        Self {
+            text: plain,
+            muted: with(Modifier::DIM),
+            accent: with(Modifier::BOLD),
+            highlight: with(Modifier::REVERSED),
+            marked: with(Modifier::BOLD),
+            heading: with(Modifier::BOLD),
+            value: with(Modifier::ITALIC),
+            search_hit: with(Modifier::UNDERLINED),
+            action: plain,
+            danger: plain,
+            success: plain,
+            error: with(Modifier::BOLD),
+            gauge: with(Modifier::BOLD),
+            chart: plain,
+            chart_value: with(Modifier::REVERSED),
+        }
+    }
+    fn slot_mut(&mut self, name: &str) -> Option<&mut Style> {
+        match name {
+            "text" => Some(&mut self.text),
+            "muted" => Some(&mut self.muted),
+            "accent" => Some(&mut self.accent),
+            "highlight" => Some(&mut self.highlight),
+            "marked" => Some(&mut self.marked),
+            "heading" => Some(&mut self.heading),
+            "value" => Some(&mut self.value),
+            "search_hit" => Some(&mut self.search_hit),
+            "action" => Some(&mut self.action),
+            "danger" => Some(&mut self.danger),
+            "success" => Some(&mut self.success),
+            "error" => Some(&mut self.error),
+            "gauge" => Some(&mut self.gauge),
+            "chart" => Some(&mut self.chart),
+            "chart_value" => Some(&mut self.chart_value),
+            _ => None,
+        }
+    }
+}
+
+// https://no-color.org
+pub fn no_color() -> bool {
+    env::var("NO_COLOR").is_ok_and(|v| !v.is_empty())
+}
+
+// the built-in themes, then the themes of `themes_folder_path`, a theme file looks like
+// { "base": "dark", "styles": { "highlight": { "fg": "green", "modifiers": ["bold"] }, "text": "white" } }
+// also returns the problems found in the theme files
+pub fn load_themes(themes_folder_path: &Path) -> (Vec<(String, Theme)>, Vec<String>) {
+    let mut themes = vec![
+        ("dark".to_string(), Theme::dark()),
+        ("light".to_string(), Theme::light()),
+        ("monochrome".to_string(), Theme::monochrome()),
+    ];
+    let mut problems = vec![];
+    let mut theme_paths: Vec<_> = match fs::read_dir(themes_folder_path) {
+        Ok(read_dir) => read_dir
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "json"))
+            .collect(),
+        Err(_) => vec![],
+    };
+    theme_paths.sort();
+    for theme_path in theme_paths {
+        let name = theme_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let data: Value = match fs::read_to_string(&theme_path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_str(&data).map_err(|e| e.to_string()))
+        {
+            Ok(data) => data,
+            Err(e) => {
+                problems.push(format!("theme {}: {}", name, e));
+                continue;
+            }
+        };
+        let base_name = data["base"].as_str().unwrap_or("dark");
+        let mut theme = match themes.iter().find(|(n, _)| n == base_name) {
+            Some((_, base)) => *base,
+            None => {
+                problems.push(format!("theme {}: unknown base {}", name, base_name));
+                Theme::dark()
+            }
+        };
+        if let Some(styles) = data["styles"].as_object() {
+            for (slot_name, spec) in styles {
+                let style = parse_style(spec);
+                match (theme.slot_mut(slot_name), style) {
+                    (Some(slot), Some(style)) => *slot = style,
+                    (None, _) => {
+                        problems.push(format!("theme {}: unknown slot {}", name, slot_name))
+                    }
+                    (_, None) => problems.push(format!("theme {}: invalid {}", name, slot_name)),
+                }
+            }
+        }
+        themes.retain(|(n, _)| *n != name);
+        themes.push((name, theme));
+    }
+    (themes, problems)
+}
+
+// "yellow", or { "fg": "#ffaa00", "bg": "black", "modifiers": ["bold", "italic"] }
+fn parse_style(spec: &Value) -> Option<Style> {
+    if let Some(color) = spec.as_str() {
+        return Some(Style::default().fg(parse_color(color)?));
+    }
+    let spec = spec.as_object()?;
+    let mut style = Style::default();
+    if let Some(fg) = spec.get("fg") {
+        style = style.fg(parse_color(fg.as_str()?)?);
+    }
+    if let Some(bg) = spec.get("bg") {
+        style = style.bg(parse_color(bg.as_str()?)?);
+    }
+    if let Some(modifiers) = spec.get("modifiers") {
+        for modifier in modifiers.as_array()? {
+            style = style.add_modifier(match modifier.as_str()? {
+                "bold" => Modifier::BOLD,
+                "dim" => Modifier::DIM,
+                "italic" => Modifier::ITALIC,
+                "underlined" => Modifier::UNDERLINED,
+                "reversed" => Modifier::REVERSED,
+                "crossed_out" => Modifier::CROSSED_OUT,
+                _ => return None,
+            });
+        }
+    }
+    Some(style)
+}
+
+// color names, "#rrggbb" or a 256 color index
+fn parse_color(text: &str) -> Option<Color> {
+    let color = match text {
+        "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" => Color::Gray,
+        "dark_gray" => Color::DarkGray,
+        "light_red" => Color::LightRed,
+        "light_green" => Color::LightGreen,
+        "light_yellow" => Color::LightYellow,
+        "light_blue" => Color::LightBlue,
+        "light_magenta" => Color::LightMagenta,
+        "light_cyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => match text.strip_prefix('#') {
+            Some(hex) if hex.len() == 6 => {
+                let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+                Color::Rgb(channel(0)?, channel(2)?, channel(4)?)
+            }
+            Some(_) => return None,
+            None => Color::Indexed(text.parse().ok()?),
+        },
+    };
+    Some(color)
+}
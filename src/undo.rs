@@ -0,0 +1,267 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{columns::SongSort, sources::Source, tags::TagChange, Song};
+
+// each edit may hold a copy of the default playlist, so not too many
+const UNDO_CAPACITY: usize = 20;
+
+// what playlist.json keeps of a playlist
+#[derive(Clone, PartialEq)]
+pub struct PlaylistContent {
+    pub songs: Vec<Song>,
+    pub sort: Option<SongSort>,
+}
+
+// the saved state of the library, what the edits are found from
+pub struct Snapshot {
+    pub playlists: Vec<(String, PlaylistContent)>, // the ones saved to playlist.json
+    pub duplicate_replacements: HashMap<String, String>,
+    pub sources: Vec<Source>,
+}
+
+// the parts an edit changed, as they were before it
+pub struct Edit {
+    pub playlists: Vec<(String, Option<PlaylistContent>)>, // None if the playlist did not exist
+    pub duplicate_replacements: Option<HashMap<String, String>>,
+    pub sources: Option<Vec<Source>>,
+    pub tags: Vec<(PathBuf, Vec<TagChange>)>, // the values of the tags that were written
+}
+impl Edit {
+    // the parts of `before` that `after` changed
+    pub fn between(
+        before: &Snapshot,
+        after: &Snapshot,
+        tags: Vec<(PathBuf, Vec<TagChange>)>,
+    ) -> Self {
+        let find = |snapshot: &Snapshot, name: &str| {
+            snapshot
+                .playlists
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, content)| content.clone())
+        };
+        let mut playlists = vec![];
+        for (name, _) in before.playlists.iter().chain(after.playlists.iter()) {
+            if playlists.iter().any(|(n, _)| n == name) {
+                continue;
+            }
+            let old = find(before, name);
+            if old != find(after, name) {
+                playlists.push((name.clone(), old));
+            }
+        }
+        Self {
+            playlists,
+            duplicate_replacements: Some(before.duplicate_replacements.clone())
+                .filter(|d| *d != after.duplicate_replacements),
+            sources: Some(before.sources.clone()).filter(|s| *s != after.sources),
+            tags,
+        }
+    }
+}
+impl Edit {
+    pub fn is_empty(&self) -> bool {
+        self.playlists.is_empty()
+            && self.duplicate_replacements.is_none()
+            && self.sources.is_none()
+            && self.tags.is_empty()
+    }
+    // "playlist Mix and the sources", for the undo and redo messages
+    pub fn describe(&self) -> String {
+        let mut parts = vec![];
+        match self.playlists.as_slice() {
+            [] => {}
+            [(name, _)] => parts.push(format!("playlist {}", name)),
+            playlists => parts.push(format!("{} playlists", playlists.len())),
+        }
+        if self.sources.is_some() {
+            parts.push("the sources".to_string());
+        }
+        if !self.tags.is_empty() {
+            parts.push(format!("the tags of {} songs", self.tags.len()));
+        }
+        match parts.split_last() {
+            Some((last, [])) => last.clone(),
+            Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+            None => "the duplicates".to_string(),
+        }
+    }
+}
+
+// the edits since the library was loaded, newest last, and the ones undone
+pub struct UndoHistory {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    current: Snapshot, // after the last recorded edit
+}
+impl UndoHistory {
+    pub fn new() -> Self {
+        Self {
+            undo: vec![],
+            redo: vec![],
+            current: Snapshot {
+                playlists: vec![],
+                duplicate_replacements: HashMap::new(),
+                sources: vec![],
+            },
+        }
+    }
+}
+impl UndoHistory {
+    // the library was loaded or scanned again, the edits before can not be undone
+    pub fn reset(&mut self, current: Snapshot) {
+        self.undo.clear();
+        self.redo.clear();
+        self.current = current;
+    }
+    // something was saved, what it changed can be undone and the undone edits are dropped
+    pub fn record(&mut self, current: Snapshot, tags: Vec<(PathBuf, Vec<TagChange>)>) {
+        let edit = Edit::between(&self.current, &current, tags);
+        self.current = current;
+        if edit.is_empty() {
+            return;
+        }
+        if self.undo.len() == UNDO_CAPACITY {
+            self.undo.remove(0);
+        }
+        self.undo.push(edit);
+        self.redo.clear();
+    }
+    pub fn current(&self) -> &Snapshot {
+        &self.current
+    }
+    pub fn take_undo(&mut self) -> Option<Edit> {
+        self.undo.pop()
+    }
+    pub fn take_redo(&mut self) -> Option<Edit> {
+        self.redo.pop()
+    }
+    // an edit was undone, `inverse` puts it back
+    pub fn undone(&mut self, inverse: Edit, current: Snapshot) {
+        self.redo.push(inverse);
+        self.current = current;
+    }
+    // an edit was redone, `inverse` undoes it again
+    pub fn redone(&mut self, inverse: Edit, current: Snapshot) {
+        self.undo.push(inverse);
+        self.current = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tags::TagField;
+
+    fn playlist(name: &str, songs: &[&str]) -> (String, PlaylistContent) {
+        let content = PlaylistContent {
+            songs: songs
+                .iter()
+                .map(|s| Song::untagged(&format!("/music/{}", s)))
+                .collect(),
+            sort: None,
+        };
+        (name.to_string(), content)
+    }
+
+    fn snapshot(playlists: Vec<(String, PlaylistContent)>, sources: &[&str]) -> Snapshot {
+        Snapshot {
+            playlists,
+            duplicate_replacements: HashMap::new(),
+            sources: sources.iter().map(|s| Source::new(s.to_string())).collect(),
+        }
+    }
+
+    fn playlist_names(edit: &Edit) -> Vec<&str> {
+        edit.playlists.iter().map(|(n, _)| n.as_str()).collect()
+    }
+
+    #[test]
+    fn between_keeps_only_what_changed() {
+        let before = snapshot(
+            vec![playlist("Default", &["a.mp3"]), playlist("Mix", &["a.mp3"])],
+            &["/music"],
+        );
+        let after = snapshot(
+            vec![playlist("Default", &["a.mp3"]), playlist("Mix", &[])],
+            &["/music"],
+        );
+        let edit = Edit::between(&before, &after, vec![]);
+        assert_eq!(playlist_names(&edit), ["Mix"]);
+        assert!(edit.playlists[0].1 == Some(before.playlists[1].1.clone()));
+        assert!(edit.sources.is_none());
+        assert!(edit.duplicate_replacements.is_none());
+        assert_eq!(edit.describe(), "playlist Mix");
+    }
+
+    #[test]
+    fn a_created_playlist_had_no_content_before() {
+        let before = snapshot(vec![playlist("Default", &[])], &[]);
+        let after = snapshot(vec![playlist("Default", &[]), playlist("New", &[])], &[]);
+        let edit = Edit::between(&before, &after, vec![]);
+        assert_eq!(playlist_names(&edit), ["New"]);
+        assert!(edit.playlists[0].1.is_none());
+    }
+
+    #[test]
+    fn describe_lists_every_part() {
+        let before = snapshot(vec![playlist("A", &[]), playlist("B", &[])], &[]);
+        let after = snapshot(vec![], &["/music"]);
+        let tags = vec![(PathBuf::from("/music/a.mp3"), vec![(TagField::Title, None)])];
+        let edit = Edit::between(&before, &after, tags);
+        assert_eq!(
+            edit.describe(),
+            "2 playlists, the sources and the tags of 1 songs"
+        );
+    }
+
+    #[test]
+    fn nothing_changed_is_not_recorded() {
+        let mut history = UndoHistory::new();
+        history.reset(snapshot(vec![playlist("Default", &["a.mp3"])], &[]));
+        history.record(snapshot(vec![playlist("Default", &["a.mp3"])], &[]), vec![]);
+        assert!(history.take_undo().is_none());
+    }
+
+    #[test]
+    fn undo_and_redo_swap_the_edit() {
+        let mut history = UndoHistory::new();
+        history.reset(snapshot(vec![], &["/music"]));
+        history.record(snapshot(vec![], &["/music", "/more"]), vec![]);
+        let edit = history.take_undo().unwrap();
+        assert!(edit.sources.as_deref() == Some(&[Source::new("/music".to_string())][..]));
+        // what the caller puts back, and the edit that puts the undone one back
+        let restored = snapshot(vec![], &["/music"]);
+        let inverse = Edit::between(history.current(), &restored, vec![]);
+        history.undone(inverse, restored);
+        assert!(history.take_undo().is_none());
+        let redo = history.take_redo().unwrap();
+        assert_eq!(redo.sources.map(|s| s.len()), Some(2));
+    }
+
+    #[test]
+    fn a_new_edit_drops_the_undone_ones() {
+        let mut history = UndoHistory::new();
+        history.reset(snapshot(vec![], &[]));
+        history.record(snapshot(vec![], &["/a"]), vec![]);
+        let edit = history.take_undo().unwrap();
+        history.undone(edit, snapshot(vec![], &[]));
+        history.record(snapshot(vec![], &["/b"]), vec![]);
+        assert!(history.take_redo().is_none());
+        assert!(history.take_undo().is_some());
+    }
+
+    #[test]
+    fn the_oldest_edits_are_forgotten() {
+        let mut history = UndoHistory::new();
+        history.reset(snapshot(vec![], &[]));
+        for i in 0..UNDO_CAPACITY + 5 {
+            history.record(snapshot(vec![], &[&i.to_string()]), vec![]);
+        }
+        let mut undone = 0;
+        while history.take_undo().is_some() {
+            undone += 1;
+        }
+        assert_eq!(undone, UNDO_CAPACITY);
+    }
+}